@@ -0,0 +1,116 @@
+/* minigrep's C API. Generated from src/ffi.rs by cbindgen, so don't edit it by hand. */
+
+#ifndef MINIGREP_H
+#define MINIGREP_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A compiled pattern. Made by `minigrep_matcher_new` and freed by
+ * `minigrep_matcher_free`.
+ */
+typedef struct MinigrepMatcher MinigrepMatcher;
+
+/**
+ * What to search for.
+ */
+typedef struct MinigrepOptions {
+  /**
+   * The text to look for, or a regex with `regex`. NUL-terminated UTF-8.
+   */
+  const char *pattern;
+  bool regex;
+  bool case_insensitive;
+  /**
+   * Let matches run across lines.
+   */
+  bool multiline;
+} MinigrepOptions;
+
+/**
+ * One match, handed to the callback. The pointer is into the buffer being
+ * searched, so it's only good for as long as that is.
+ */
+typedef struct MinigrepMatch {
+  /**
+   * 1-based numbers of the first and last lines the match is on.
+   */
+  size_t line_number;
+  size_t end_line_number;
+  /**
+   * Byte offsets of the match in the buffer.
+   */
+  size_t start;
+  size_t end;
+  /**
+   * The whole lines the match is on, without the final line terminator.
+   * Not NUL-terminated.
+   */
+  const char *lines;
+  size_t lines_len;
+} MinigrepMatch;
+
+/**
+ * Called for every match. Return `false` to stop searching.
+ */
+typedef bool (*MinigrepCallback)(const struct MinigrepMatch *m, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Compiles a matcher, or returns NULL if it can't. Then, if `error` isn't
+ * NULL, `*error` is set to a message to free with `minigrep_error_free`.
+ *
+ * # Safety
+ *
+ * `options` has to point to valid options, and `error` has to be NULL or
+ * point to somewhere a pointer can be written.
+ */
+struct MinigrepMatcher *minigrep_matcher_new(const struct MinigrepOptions *options, char **error);
+
+/**
+ * Searches `len` bytes of UTF-8 at `buffer`, calling `callback` for every
+ * match with `user_data` passed along. Returns how many matches were
+ * reported, or -1 if an argument is NULL or the buffer isn't UTF-8.
+ *
+ * # Safety
+ *
+ * `matcher` has to come from `minigrep_matcher_new`, and `buffer` has to
+ * point to `len` readable bytes.
+ */
+int64_t minigrep_search(const struct MinigrepMatcher *matcher,
+                        const char *buffer,
+                        size_t len,
+                        MinigrepCallback callback,
+                        void *user_data);
+
+/**
+ * Frees a matcher. Does nothing if it's NULL.
+ *
+ * # Safety
+ *
+ * `matcher` has to come from `minigrep_matcher_new` and not be used again.
+ */
+void minigrep_matcher_free(struct MinigrepMatcher *matcher);
+
+/**
+ * Frees an error message from `minigrep_matcher_new`. Does nothing if it's
+ * NULL.
+ *
+ * # Safety
+ *
+ * `error` has to come from `minigrep_matcher_new` and not be used again.
+ */
+void minigrep_error_free(char *error);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MINIGREP_H */
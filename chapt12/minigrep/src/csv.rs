@@ -0,0 +1,244 @@
+//! Reading CSV and TSV, for `--csv`, `--tsv` and `--field`.
+//!
+//! Quoting follows RFC 4180: a field that starts with `"` runs to the next
+//! `"` that isn't doubled, and can have delimiters and line breaks in it.
+//! Records end at `\n` or `\r\n`. Blank lines are skipped.
+use std::ops::Range;
+
+/// One field of a record, unquoted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub value: String,
+    /// For each byte of `value`, where it came from in the record's text.
+    /// That's two bytes for a doubled quote.
+    origins: Vec<Range<usize>>,
+    /// Where the field ends in the record's text.
+    end: usize,
+}
+
+impl Field {
+    /// Where a range of the unquoted value is in the record's text.
+    pub fn raw(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.origins.get(range.start).map_or(self.end, |o| o.start);
+        if range.is_empty() {
+            return start..start;
+        }
+        start..self.origins[range.end - 1].end
+    }
+}
+
+/// A field while it's being read, a byte at a time.
+#[derive(Debug, Default)]
+struct Pending {
+    bytes: Vec<u8>,
+    origins: Vec<Range<usize>>,
+}
+
+impl Pending {
+    fn push(&mut self, byte: u8, origin: Range<usize>) {
+        self.bytes.push(byte);
+        self.origins.push(origin);
+    }
+
+    fn finish(&mut self, end: usize) -> Field {
+        let Pending { bytes, origins } = std::mem::take(self);
+        // Only ASCII quotes are ever left out, so every character is whole.
+        let value = String::from_utf8(bytes).expect("fields are copied from valid UTF-8");
+        Field {
+            value,
+            origins,
+            end,
+        }
+    }
+}
+
+/// A record, as it's written in the file and split into fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<'t> {
+    /// 1-based number of the line the record starts on.
+    pub line_number: usize,
+    /// 1-based number of the line it ends on, which is later when a quoted
+    /// field has line breaks in it.
+    pub end_line_number: usize,
+    /// Byte offset of `text` in the file.
+    pub offset: usize,
+    /// The record without its line terminator.
+    pub text: &'t str,
+    pub fields: Vec<Field>,
+}
+
+/// Splits `text` into records.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::csv::records;
+///
+/// let text = "id,note\n1,\"hi, \"\"you\"\"\nthere\"\n";
+/// let record = records(text, ',').nth(1).unwrap();
+/// assert_eq!((2, 3), (record.line_number, record.end_line_number));
+/// assert_eq!("hi, \"you\"\nthere", record.fields[1].value);
+/// ```
+pub fn records(text: &str, delimiter: char) -> Records<'_> {
+    assert!(
+        delimiter.is_ascii() && delimiter != '"',
+        "unsupported delimiter"
+    );
+    Records {
+        text,
+        delimiter: delimiter as u8,
+        at: 0,
+        line_number: 1,
+    }
+}
+
+/// An iterator over the records in some CSV, made by [`records`].
+#[derive(Debug, Clone)]
+pub struct Records<'t> {
+    text: &'t str,
+    delimiter: u8,
+    at: usize,
+    line_number: usize,
+}
+
+impl<'t> Iterator for Records<'t> {
+    type Item = Record<'t>;
+
+    fn next(&mut self) -> Option<Record<'t>> {
+        while self.at < self.text.len() {
+            let record = self.record();
+            if !record.text.is_empty() {
+                return Some(record);
+            }
+        }
+        None
+    }
+}
+
+impl<'t> Records<'t> {
+    fn record(&mut self) -> Record<'t> {
+        let bytes = self.text.as_bytes();
+        let start = self.at;
+        let line_number = self.line_number;
+        let mut fields = Vec::new();
+        let mut field = Pending::default();
+        // Whether anything of the current field has been seen, since only a
+        // quote at the very start opens a quoted field.
+        let mut started = false;
+        let mut quoted = false;
+        let mut i = start;
+
+        let end = loop {
+            let Some(&byte) = bytes.get(i) else {
+                self.at = i;
+                break i;
+            };
+            if quoted {
+                match byte {
+                    b'"' if bytes.get(i + 1) == Some(&b'"') => {
+                        field.push(b'"', i - start..i - start + 2);
+                        i += 1;
+                    }
+                    b'"' => quoted = false,
+                    _ => {
+                        if byte == b'\n' {
+                            self.line_number += 1;
+                        }
+                        field.push(byte, i - start..i - start + 1);
+                    }
+                }
+            } else if byte == b'"' && !started {
+                quoted = true;
+            } else if byte == self.delimiter {
+                fields.push(field.finish(i - start));
+                started = false;
+                i += 1;
+                continue;
+            } else if byte == b'\n' || (byte == b'\r' && bytes.get(i + 1) == Some(&b'\n')) {
+                self.at = if byte == b'\r' { i + 2 } else { i + 1 };
+                self.line_number += 1;
+                break i;
+            } else {
+                field.push(byte, i - start..i - start + 1);
+            }
+            started = true;
+            i += 1;
+        };
+        fields.push(field.finish(end - start));
+        Record {
+            line_number,
+            end_line_number: self.line_number - usize::from(self.at > end),
+            offset: start,
+            text: &self.text[start..end],
+            fields,
+        }
+    }
+}
+
+/// Which columns `--field` picks, given a file's header. Each field is a
+/// column name, or a 1-based column number if no column has that name.
+///
+/// Returns the first field that isn't a column of the header if there is
+/// one, whether it's a name or a number.
+pub fn columns<'f>(header: &Record, fields: &'f [String]) -> Result<Vec<usize>, &'f str> {
+    fields
+        .iter()
+        .map(|field| {
+            let named = header.fields.iter().position(|f| f.value == *field);
+            let numbered = field
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0 && n <= header.fields.len())
+                .map(|n| n - 1);
+            named.or(numbered).ok_or(field.as_str())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<'r>(record: &'r Record) -> Vec<&'r str> {
+        record.fields.iter().map(|f| f.value.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_quoted_fields_across_lines() {
+        let text = "name,quote\r\n\"Ferris\",\"say \"\"hi\"\"\"\r\n\r\nZoë,\"a,\nb\"\nlast,";
+        let found: Vec<_> = records(text, ',').collect();
+        assert_eq!(4, found.len());
+        assert_eq!(vec!["Ferris", "say \"hi\""], values(&found[1]));
+        assert_eq!((2, 2), (found[1].line_number, found[1].end_line_number));
+        assert_eq!(vec!["Zoë", "a,\nb"], values(&found[2]));
+        assert_eq!((4, 5), (found[2].line_number, found[2].end_line_number));
+        assert_eq!("Zoë,\"a,\nb\"", found[2].text);
+
+        assert_eq!(vec!["last", ""], values(&found[3]));
+        assert_eq!(6, found[3].line_number);
+        let tsv: Vec<_> = records("a\tb,c\n", '\t').collect();
+        assert_eq!(vec!["a", "b,c"], values(&tsv[0]));
+    }
+
+    #[test]
+    fn maps_unquoted_ranges_back_to_the_text() {
+        let record = records("1,\"x \"\"é\"\" y\"", ',').next().unwrap();
+        let field = &record.fields[1];
+        let at = field.value.find("\"é\"").unwrap();
+        let raw = field.raw(at..at + 4);
+        assert_eq!("\"\"é\"\"", &record.text[raw]);
+        assert_eq!(record.text.len()..record.text.len(), field.raw(8..8));
+    }
+
+    #[test]
+    fn picks_columns_by_name_or_number() {
+        let header = records("id,name,2", ',').next().unwrap();
+        let fields = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(Ok(vec![1, 0]), columns(&header, &fields(&["name", "1"])));
+        assert_eq!(Ok(vec![2]), columns(&header, &fields(&["2"])));
+        assert_eq!(Err("email"), columns(&header, &fields(&["email"])));
+        assert_eq!(Err("0"), columns(&header, &fields(&["0"])));
+        assert_eq!(Ok(vec![2]), columns(&header, &fields(&["3"])));
+        assert_eq!(Err("4"), columns(&header, &fields(&["4"])));
+    }
+}
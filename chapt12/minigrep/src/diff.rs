@@ -0,0 +1,267 @@
+//! Reading unified diffs, for `--diff`.
+//!
+//! Plain `diff -u` output works, and so does git's, with its extra headers.
+//! Hunk headers say how many lines each hunk has. That means a removed line
+//! that starts with `--` is never mistaken for the next file's header.
+
+/// A line a diff adds or removes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line<'d> {
+    /// Its number in the new file if it was added, or in the old file if it
+    /// was removed.
+    pub line_number: usize,
+    /// Byte offset of `text` in the diff.
+    pub offset: usize,
+    /// The line without its `+` or `-` or its line terminator.
+    pub text: &'d str,
+}
+
+/// The changes a diff makes to one file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDiff<'d> {
+    /// The file's path after the change, or before it if the file was
+    /// deleted, without git's `a/` or `b/` in front.
+    pub path: &'d str,
+    pub added: Vec<Line<'d>>,
+    pub removed: Vec<Line<'d>>,
+}
+
+/// Where the searcher is in the current hunk.
+#[derive(Debug, Default)]
+struct Hunk {
+    old_line: usize,
+    new_line: usize,
+    old_left: usize,
+    new_left: usize,
+}
+
+impl Hunk {
+    /// Parses the `-1,5 +1,6 @@` after a hunk's `@@ `.
+    fn parse(header: &str) -> Option<Hunk> {
+        let mut parts = header.split_whitespace();
+        let (old_line, old_left) = range(parts.next()?.strip_prefix('-')?)?;
+        let (new_line, new_left) = range(parts.next()?.strip_prefix('+')?)?;
+        Some(Hunk {
+            old_line,
+            new_line,
+            old_left,
+            new_left,
+        })
+    }
+
+    fn is_done(&self) -> bool {
+        self.old_left == 0 && self.new_left == 0
+    }
+}
+
+/// `start,count`, where the count is 1 if it's left out.
+fn range(s: &str) -> Option<(usize, usize)> {
+    match s.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((s.parse().ok()?, 1)),
+    }
+}
+
+/// The path in a `---` or `+++` header, or `None` for `/dev/null`.
+fn header_path<'d>(header: &'d str, prefix: &str) -> Option<&'d str> {
+    // Some tools put a tab and a timestamp after the path.
+    let path = header.split('\t').next().unwrap_or(header);
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path))
+}
+
+/// Splits a diff into the changes it makes to each file.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::diff;
+///
+/// let patch = "\
+/// --- a/src/main.rs
+/// +++ b/src/main.rs
+/// @@ -10,2 +10,2 @@ fn main() {
+///      let config = parse();
+/// -    run(config);
+/// +    run(config).unwrap();
+/// ";
+/// let files = diff::parse(patch);
+/// assert_eq!("src/main.rs", files[0].path);
+/// assert_eq!(11, files[0].added[0].line_number);
+/// assert_eq!("    run(config).unwrap();", files[0].added[0].text);
+/// ```
+pub fn parse(text: &str) -> Vec<FileDiff<'_>> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut hunk = Hunk::default();
+    let mut old_path = None;
+    // Whether the last file came from a `diff --git` line and is still
+    // waiting for its `---` and `+++` headers, if it has any.
+    let mut from_git = false;
+    let mut offset = 0;
+
+    for raw in text.split_inclusive('\n') {
+        let start = offset;
+        offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        if let (false, Some(file)) = (hunk.is_done(), files.last_mut()) {
+            let changed = || Line {
+                line_number: 0,
+                offset: start + 1,
+                text: &line[1..],
+            };
+            match line.as_bytes().first() {
+                Some(b'+') => {
+                    file.added.push(Line {
+                        line_number: hunk.new_line,
+                        ..changed()
+                    });
+                    hunk.new_line += 1;
+                    hunk.new_left = hunk.new_left.saturating_sub(1);
+                }
+                Some(b'-') => {
+                    file.removed.push(Line {
+                        line_number: hunk.old_line,
+                        ..changed()
+                    });
+                    hunk.old_line += 1;
+                    hunk.old_left = hunk.old_left.saturating_sub(1);
+                }
+                // `\ No newline at end of file`
+                Some(b'\\') => {}
+                // Context, which some tools send with its leading space
+                // trimmed off when the line is blank.
+                _ => {
+                    hunk.old_line += 1;
+                    hunk.new_line += 1;
+                    hunk.old_left = hunk.old_left.saturating_sub(1);
+                    hunk.new_left = hunk.new_left.saturating_sub(1);
+                }
+            }
+            continue;
+        }
+
+        if let Some(names) = line.strip_prefix("diff --git ") {
+            // Renames and mode changes have no `+++` line, so the path comes
+            // from here to begin with.
+            let path = names.rfind(" b/").map_or(names, |at| &names[at + 3..]);
+            files.push(FileDiff {
+                path,
+                ..FileDiff::default()
+            });
+            from_git = true;
+            old_path = None;
+        } else if let Some(header) = line.strip_prefix("--- ") {
+            old_path = header_path(header, "a/");
+        } else if let Some(header) = line.strip_prefix("+++ ") {
+            let path = header_path(header, "b/").or(old_path).unwrap_or_default();
+            if from_git {
+                if let Some(file) = files.last_mut() {
+                    file.path = path;
+                }
+            } else {
+                files.push(FileDiff {
+                    path,
+                    ..FileDiff::default()
+                });
+            }
+            from_git = false;
+        } else if let Some(header) = line.strip_prefix("@@ ") {
+            if let Some(next) = Hunk::parse(header) {
+                if files.is_empty() {
+                    files.push(FileDiff::default());
+                }
+                hunk = next;
+                from_git = false;
+            }
+        }
+        // Anything else is a header this doesn't need, or commentary.
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes<'d>(lines: &[Line<'d>]) -> Vec<(usize, &'d str)> {
+        lines.iter().map(|l| (l.line_number, l.text)).collect()
+    }
+
+    #[test]
+    fn reads_git_diffs_with_new_deleted_and_renamed_files() {
+        let patch = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 3b18e51..a9c4d2f 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@
+ use std::fs;
++use std::process;
+
+-fn old() {}
++fn new() { process::exit(1) }
+@@ -20,0 +22,1 @@ impl Config {
++    // added
+diff --git a/old.txt b/new.txt
+similarity index 100%
+rename from old.txt
+rename to new.txt
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-unsafe { gone() }
+diff --git a/fresh.rs b/fresh.rs
+new file mode 100644
+--- /dev/null
++++ b/fresh.rs
+@@ -0,0 +1,2 @@
++fn fresh() {}
++
+";
+        let files = parse(patch);
+        let paths: Vec<_> = files.iter().map(|f| f.path).collect();
+        assert_eq!(vec!["src/lib.rs", "new.txt", "gone.rs", "fresh.rs"], paths);
+        assert_eq!(
+            vec![
+                (2, "use std::process;"),
+                (4, "fn new() { process::exit(1) }"),
+                (22, "    // added")
+            ],
+            changes(&files[0].added)
+        );
+        assert_eq!(vec![(3, "fn old() {}")], changes(&files[0].removed));
+        assert!(files[1].added.is_empty() && files[1].removed.is_empty());
+        assert_eq!(vec![(1, "unsafe { gone() }")], changes(&files[2].removed));
+        assert_eq!(
+            vec![(1, "fn fresh() {}"), (2, "")],
+            changes(&files[3].added)
+        );
+
+        let at = files[0].added[0].offset;
+        assert_eq!("use std::process;\n", &patch[at..at + 18]);
+    }
+
+    #[test]
+    fn lines_that_look_like_headers_stay_in_their_hunk() {
+        let patch = "\
+--- notes.md\t2024-05-01 14:00:00.000000000 +0200
++++ notes.md\t2024-05-01 14:05:00.000000000 +0200
+@@ -1,2 +1,2 @@
+--- a rule
++++ a bigger rule\r
+ end
+\\ No newline at end of file
+";
+        let files = parse(patch);
+        assert_eq!(1, files.len());
+        assert_eq!("notes.md", files[0].path);
+        assert_eq!(vec![(1, "-- a rule")], changes(&files[0].removed));
+        assert_eq!(vec![(1, "++ a bigger rule")], changes(&files[0].added));
+    }
+}
@@ -0,0 +1,171 @@
+//! A C API, built into the `cdylib` and `staticlib` versions of the crate.
+//!
+//! `include/minigrep.h` is generated from this file with cbindgen, and a test
+//! checks it's up to date. `tests/ffi.c` shows how it's used.
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use std::slice;
+
+use crate::matcher::{CaseInsensitiveMatcher, LiteralMatcher, Matcher, RegexMatcher};
+use crate::searcher::{Match, Searcher, SearcherBuilder};
+
+/// What to search for.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MinigrepOptions {
+    /// The text to look for, or a regex with `regex`. NUL-terminated UTF-8.
+    pub pattern: *const c_char,
+    pub regex: bool,
+    pub case_insensitive: bool,
+    /// Let matches run across lines.
+    pub multiline: bool,
+}
+
+/// A compiled pattern. Made by `minigrep_matcher_new` and freed by
+/// `minigrep_matcher_free`.
+pub struct MinigrepMatcher {
+    matcher: Box<dyn Matcher>,
+    searcher: Searcher,
+}
+
+/// One match, handed to the callback. The pointer is into the buffer being
+/// searched, so it's only good for as long as that is.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MinigrepMatch {
+    /// 1-based numbers of the first and last lines the match is on.
+    pub line_number: usize,
+    pub end_line_number: usize,
+    /// Byte offsets of the match in the buffer.
+    pub start: usize,
+    pub end: usize,
+    /// The whole lines the match is on, without the final line terminator.
+    /// Not NUL-terminated.
+    pub lines: *const c_char,
+    pub lines_len: usize,
+}
+
+/// Called for every match. Return `false` to stop searching.
+pub type MinigrepCallback =
+    Option<unsafe extern "C" fn(m: *const MinigrepMatch, user_data: *mut c_void) -> bool>;
+
+/// Compiles a matcher, or returns NULL if it can't. Then, if `error` isn't
+/// NULL, `*error` is set to a message to free with `minigrep_error_free`.
+///
+/// # Safety
+///
+/// `options` has to point to valid options, and `error` has to be NULL or
+/// point to somewhere a pointer can be written.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_matcher_new(
+    options: *const MinigrepOptions,
+    error: *mut *mut c_char,
+) -> *mut MinigrepMatcher {
+    let fail = |message: String| {
+        if !error.is_null() {
+            let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+            *error = message.into_raw();
+        }
+        ptr::null_mut()
+    };
+    let Some(options) = options.as_ref() else {
+        return fail("options is NULL".to_string());
+    };
+    if options.pattern.is_null() {
+        return fail("the pattern is NULL".to_string());
+    }
+    let Ok(pattern) = CStr::from_ptr(options.pattern).to_str() else {
+        return fail("the pattern isn't valid UTF-8".to_string());
+    };
+    let matcher: Box<dyn Matcher> = if options.regex {
+        match RegexMatcher::new(pattern, !options.case_insensitive) {
+            Ok(matcher) => Box::new(matcher),
+            Err(e) => return fail(e.to_string()),
+        }
+    } else if options.case_insensitive {
+        Box::new(CaseInsensitiveMatcher::new(pattern))
+    } else {
+        Box::new(LiteralMatcher::new(pattern))
+    };
+    let searcher = SearcherBuilder::new().multiline(options.multiline).build();
+    Box::into_raw(Box::new(MinigrepMatcher { matcher, searcher }))
+}
+
+/// Searches `len` bytes of UTF-8 at `buffer`, calling `callback` for every
+/// match with `user_data` passed along. Returns how many matches were
+/// reported, or -1 if an argument is NULL or the buffer isn't UTF-8.
+///
+/// # Safety
+///
+/// `matcher` has to come from `minigrep_matcher_new`, and `buffer` has to
+/// point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_search(
+    matcher: *const MinigrepMatcher,
+    buffer: *const c_char,
+    len: usize,
+    callback: MinigrepCallback,
+    user_data: *mut c_void,
+) -> i64 {
+    let (Some(matcher), Some(callback)) = (matcher.as_ref(), callback) else {
+        return -1;
+    };
+    if buffer.is_null() && len > 0 {
+        return -1;
+    }
+    let bytes = match len {
+        0 => &[][..],
+        _ => slice::from_raw_parts(buffer.cast::<u8>(), len),
+    };
+    let Ok(haystack) = std::str::from_utf8(bytes) else {
+        return -1;
+    };
+
+    let mut reported = 0;
+    let result: Result<(), ()> =
+        matcher
+            .searcher
+            .search(&matcher.matcher, haystack, &mut |m: &Match| {
+                for range in &m.ranges {
+                    let found = MinigrepMatch {
+                        line_number: m.line_number,
+                        end_line_number: m.end_line_number,
+                        start: m.offset + range.start,
+                        end: m.offset + range.end,
+                        lines: m.text.as_ptr().cast(),
+                        lines_len: m.text.len(),
+                    };
+                    reported += 1;
+                    if !callback(&found, user_data) {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            });
+    result.map_or(-1, |_| reported)
+}
+
+/// Frees a matcher. Does nothing if it's NULL.
+///
+/// # Safety
+///
+/// `matcher` has to come from `minigrep_matcher_new` and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_matcher_free(matcher: *mut MinigrepMatcher) {
+    if !matcher.is_null() {
+        drop(Box::from_raw(matcher));
+    }
+}
+
+/// Frees an error message from `minigrep_matcher_new`. Does nothing if it's
+/// NULL.
+///
+/// # Safety
+///
+/// `error` has to come from `minigrep_matcher_new` and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_error_free(error: *mut c_char) {
+    if !error.is_null() {
+        drop(CString::from_raw(error));
+    }
+}
@@ -0,0 +1,218 @@
+//! Following a file as it grows, like `tail -F`.
+//!
+//! A [`Follower`] remembers how far into the file it has read. Each call to
+//! [`Follower::poll`] hands back whatever complete lines have been appended
+//! since, along with the line still being written, starting over if the file
+//! was truncated or replaced (log rotation).
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long `minigrep --follow` waits between polls.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// New text read by [`Follower::poll`].
+#[derive(Debug, Default, PartialEq)]
+pub struct Update {
+    /// Complete lines that appeared since the last poll.
+    pub text: String,
+    /// 1-based line number of the first line in `text`.
+    pub first_line: usize,
+    /// The line after `text`, which doesn't have its newline yet. It's
+    /// handed back again, with whatever was added to it, until it does.
+    pub partial: String,
+    /// The file got shorter, so we started again from the top.
+    pub truncated: bool,
+    /// A different file now lives at the path, so we switched to it.
+    pub rotated: bool,
+}
+
+pub struct Follower {
+    path: PathBuf,
+    file: File,
+    id: Option<FileId>,
+    offset: u64,
+    /// Bytes after the last newline we've seen, waiting for the rest of the line.
+    partial: Vec<u8>,
+    /// Number of the next complete line we'll hand out.
+    line: usize,
+}
+
+impl Follower {
+    /// Starts following the file at `path` from the beginning.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Follower> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let id = file_id(&file.metadata()?);
+        Ok(Follower {
+            path,
+            file,
+            id,
+            offset: 0,
+            partial: Vec::new(),
+            line: 1,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads whatever was appended since the last poll.
+    ///
+    /// A trailing line without a newline yet goes in `partial` rather than
+    /// `text`, and only counts as a line once it's finished.
+    /// If the path has gone missing, e.g. halfway through a rotation, the old
+    /// file is still drained and the path is checked again next time. Once a
+    /// new file shows up, the rest of the old one is returned with `rotated`
+    /// set and the new file is read from the top on the following poll.
+    pub fn poll(&mut self) -> io::Result<Update> {
+        let mut update = Update::default();
+        let mut text = Vec::new();
+
+        match fs::metadata(&self.path) {
+            Ok(metadata) if file_id(&metadata) != self.id => {
+                // Finish off the old file first; the new one is read next time.
+                self.read_new(&mut text)?;
+                if !text.is_empty() && !text.ends_with(b"\n") {
+                    text.push(b'\n');
+                }
+                update.text = String::from_utf8_lossy(&text).into_owned();
+                update.first_line = self.line;
+                update.rotated = true;
+
+                self.file = File::open(&self.path)?;
+                self.id = file_id(&self.file.metadata()?);
+                self.offset = 0;
+                self.line = 1;
+                return Ok(update);
+            }
+            Ok(metadata) if metadata.len() < self.offset => {
+                self.offset = 0;
+                self.partial.clear();
+                self.line = 1;
+                update.truncated = true;
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        self.read_new(&mut text)?;
+        let complete = match text.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        self.partial.extend(text.drain(complete..));
+
+        update.text = String::from_utf8_lossy(&text).into_owned();
+        update.first_line = self.line;
+        update.partial = String::from_utf8_lossy(&self.partial).into_owned();
+        self.line += update.text.lines().count();
+        Ok(update)
+    }
+
+    /// Appends everything past `offset` in the current file to `buf`.
+    fn read_new(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        buf.append(&mut self.partial);
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let read = self.file.read_to_end(buf)?;
+        self.offset += read as u64;
+        Ok(())
+    }
+}
+
+/// Identifies the file behind a path, so we can tell when it's been replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileId {
+    pub(crate) dev: u64,
+    ino: u64,
+}
+
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some(FileId {
+        dev: metadata.dev(),
+        ino: metadata.ino(),
+    })
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &fs::Metadata) -> Option<FileId> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn returns_appended_lines_once_complete() {
+        let path = temp_path("follow-append.log");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        let mut follower = Follower::new(&path).unwrap();
+
+        assert_eq!("one\ntwo\n", follower.poll().unwrap().text);
+        append(&path, "thr");
+        let update = follower.poll().unwrap();
+        assert_eq!("", update.text);
+        assert_eq!("thr", update.partial);
+        append(&path, "ee\nfour");
+        let update = follower.poll().unwrap();
+        assert_eq!("three\n", update.text);
+        assert_eq!(3, update.first_line);
+        assert_eq!("four", update.partial);
+        assert_eq!("four", follower.poll().unwrap().partial);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn starts_over_after_truncation() {
+        let path = temp_path("follow-truncate.log");
+        fs::write(&path, "a long first line\n").unwrap();
+        let mut follower = Follower::new(&path).unwrap();
+        follower.poll().unwrap();
+
+        fs::write(&path, "short\n").unwrap();
+        let update = follower.poll().unwrap();
+        assert!(update.truncated);
+        assert_eq!("short\n", update.text);
+        assert_eq!(1, update.first_line);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn switches_to_the_new_file_after_rotation() {
+        let path = temp_path("follow-rotate.log");
+        let rotated = temp_path("follow-rotate.log.1");
+        fs::write(&path, "old\n").unwrap();
+        let mut follower = Follower::new(&path).unwrap();
+        follower.poll().unwrap();
+
+        append(&path, "last old line\n");
+        fs::rename(&path, &rotated).unwrap();
+        assert_eq!("last old line\n", follower.poll().unwrap().text);
+
+        fs::write(&path, "new\n").unwrap();
+        let update = follower.poll().unwrap();
+        assert!(update.rotated);
+        assert_eq!("", update.text);
+        assert_eq!("new\n", follower.poll().unwrap().text);
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(rotated).unwrap();
+    }
+}
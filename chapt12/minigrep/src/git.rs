@@ -0,0 +1,884 @@
+//! Reading files out of a git repository without running git, for `--rev`.
+//!
+//! This goes straight to the `.git` directory: a revision is resolved through
+//! loose and packed refs, the commit's tree is walked, and blobs are read
+//! whether they're loose zlib-compressed objects or live in a packfile,
+//! possibly stored as deltas against other objects. It only knows enough of
+//! the format to read, and never writes anything.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+use memmap2::Mmap;
+
+/// How many `ref: ...` links to follow before deciding they go round in a
+/// circle.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// How long a chain of deltas can get before it's taken to be broken, or to
+/// go round in a circle. git stops at 50 unless it's told otherwise.
+const MAX_DELTA_DEPTH: usize = 1000;
+
+/// How many bytes of delta bases to keep around between objects.
+const BASE_CACHE_SIZE: usize = 32 << 20;
+
+/// The SHA-1 that names an object.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Oid([u8; 20]);
+
+impl Oid {
+    /// Parses a full 40 character hex id.
+    pub fn from_hex(hex: &str) -> Option<Oid> {
+        if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bytes = [0; 20];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+        }
+        Some(Oid(bytes))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Oid> {
+        bytes.try_into().ok().map(Oid)
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl fmt::Debug for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// The four kinds of object git stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_name(name: &[u8]) -> Option<Kind> {
+        match name {
+            b"commit" => Some(Kind::Commit),
+            b"tree" => Some(Kind::Tree),
+            b"blob" => Some(Kind::Blob),
+            b"tag" => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+
+    /// The kind for the type number in a pack entry's header.
+    fn from_pack_type(number: u8) -> Option<Kind> {
+        match number {
+            1 => Some(Kind::Commit),
+            2 => Some(Kind::Tree),
+            3 => Some(Kind::Blob),
+            4 => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Commit => "commit",
+            Kind::Tree => "tree",
+            Kind::Blob => "blob",
+            Kind::Tag => "tag",
+        })
+    }
+}
+
+/// An object's kind and its contents, without the header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub kind: Kind,
+    pub data: Vec<u8>,
+}
+
+/// A repository opened for reading.
+///
+/// # Examples
+///
+/// ```no_run
+/// use minigrep::git::Repository;
+///
+/// let repo = Repository::discover(".").unwrap();
+/// let tree = repo.tree(repo.resolve("v1.0~2").unwrap()).unwrap();
+/// for (path, id) in repo.files(tree).unwrap() {
+///     let blob = repo.read(id).unwrap();
+///     println!("{}: {} bytes", path, blob.data.len());
+/// }
+/// ```
+pub struct Repository {
+    /// The top of the working tree, which paths in the repository are
+    /// relative to.
+    root: PathBuf,
+    /// Where `HEAD` lives. In a linked worktree that's not where everything
+    /// else is.
+    git_dir: PathBuf,
+    /// Where the shared refs and objects live.
+    common_dir: PathBuf,
+    /// `objects` plus any alternates it borrows objects from.
+    object_dirs: Vec<PathBuf>,
+    packs: Vec<Pack>,
+    bases: RefCell<BaseCache>,
+}
+
+impl Repository {
+    /// Opens the repository that `dir` is in, looking in its parents for a
+    /// `.git` the way git does.
+    pub fn discover(dir: impl AsRef<Path>) -> io::Result<Repository> {
+        let dir = fs::canonicalize(dir)?;
+        for root in dir.ancestors() {
+            let dot_git = root.join(".git");
+            let git_dir = if dot_git.is_dir() {
+                dot_git
+            } else if dot_git.is_file() {
+                // Linked worktrees and submodules have a file saying where
+                // the repository really is.
+                let contents = fs::read_to_string(&dot_git)?;
+                let target = contents.strip_prefix("gitdir:").ok_or_else(|| {
+                    corrupt(format!("{} doesn't start with gitdir:", dot_git.display()))
+                })?;
+                root.join(target.trim())
+            } else {
+                continue;
+            };
+            return Repository::open(root, git_dir);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} isn't inside a git repository", dir.display()),
+        ))
+    }
+
+    fn open(root: &Path, git_dir: PathBuf) -> io::Result<Repository> {
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => git_dir.join(common.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        let objects = common_dir.join("objects");
+        let mut object_dirs = vec![objects.clone()];
+        if let Ok(alternates) = fs::read_to_string(objects.join("info/alternates")) {
+            let listed = alternates
+                .lines()
+                .filter(|line| !line.is_empty() && !line.starts_with('#'));
+            object_dirs.extend(listed.map(|line| objects.join(line)));
+        }
+
+        let mut packs = Vec::new();
+        for dir in &object_dirs {
+            let Ok(entries) = fs::read_dir(dir.join("pack")) else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "idx") {
+                    packs.push(Pack::open(&path)?);
+                }
+            }
+        }
+
+        Ok(Repository {
+            root: root.to_path_buf(),
+            git_dir,
+            common_dir,
+            object_dirs,
+            packs,
+            bases: RefCell::default(),
+        })
+    }
+
+    /// The top of the working tree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Turns a path relative to the current directory into one relative to
+    /// the top of the working tree, written the way paths in a tree are. The
+    /// top itself is the empty string.
+    pub fn pathspec(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        let mut absolute = PathBuf::new();
+        for component in env::current_dir()?.join(path.as_ref()).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    absolute.pop();
+                }
+                _ => absolute.push(component),
+            }
+        }
+        let relative = absolute.strip_prefix(&self.root).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is outside the repository at {}",
+                    path.as_ref().display(),
+                    self.root.display()
+                ),
+            )
+        })?;
+        let parts: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        Ok(parts.join("/"))
+    }
+
+    /// Resolves a revision to the id of the object it names.
+    ///
+    /// A revision is a ref name (`HEAD`, `main`, `v1.0`, `origin/main`,
+    /// `refs/tags/v1.0`, ...) or an object id, which can be abbreviated to
+    /// as few as four characters. It can be followed by `~N` for the Nth
+    /// first-parent ancestor and `^N` for the Nth parent.
+    pub fn resolve(&self, rev: &str) -> io::Result<Oid> {
+        let unknown = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("unknown revision {:?}", rev),
+            )
+        };
+        let split = rev.find(['~', '^']).unwrap_or(rev.len());
+        let (name, mut suffix) = rev.split_at(split);
+        let mut oid = self.resolve_name(name)?.ok_or_else(unknown)?;
+
+        while let Some(op) = suffix.chars().next() {
+            if op != '~' && op != '^' {
+                return Err(unknown());
+            }
+            suffix = &suffix[1..];
+            let digits = suffix
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(suffix.len());
+            let n = match &suffix[..digits] {
+                "" => 1,
+                n => n.parse().map_err(|_| unknown())?,
+            };
+            suffix = &suffix[digits..];
+            oid = match (op, n) {
+                ('^', 0) => self.peel(oid, Kind::Commit)?,
+                ('^', n) => self.parent(oid, n)?.ok_or_else(unknown)?,
+                (_, n) => {
+                    for _ in 0..n {
+                        oid = self.parent(oid, 1)?.ok_or_else(unknown)?;
+                    }
+                    oid
+                }
+            };
+        }
+        Ok(oid)
+    }
+
+    /// The id a revision name stands for, without any `~` or `^`.
+    fn resolve_name(&self, name: &str) -> io::Result<Option<Oid>> {
+        if let Some(oid) = Oid::from_hex(name) {
+            return Ok(Some(oid));
+        }
+        let name = if name == "@" { "HEAD" } else { name };
+        // The same places git looks, in the same order.
+        let candidates = [
+            name.to_string(),
+            format!("refs/{}", name),
+            format!("refs/tags/{}", name),
+            format!("refs/heads/{}", name),
+            format!("refs/remotes/{}", name),
+            format!("refs/remotes/{}/HEAD", name),
+        ];
+        for candidate in &candidates {
+            if let Some(oid) = self.read_ref(candidate, 0)? {
+                return Ok(Some(oid));
+            }
+        }
+        self.find_abbreviated(name)
+    }
+
+    /// Reads a ref, following symbolic refs like `HEAD`.
+    fn read_ref(&self, name: &str, depth: usize) -> io::Result<Option<Oid>> {
+        let unsafe_part = |part: &str| part.is_empty() || part == "." || part == "..";
+        if depth > MAX_SYMREF_DEPTH || name.split('/').any(unsafe_part) {
+            return Ok(None);
+        }
+        // HEAD and other per-worktree refs are in the worktree's own git dir.
+        for dir in [&self.git_dir, &self.common_dir] {
+            if let Ok(contents) = fs::read_to_string(dir.join(name)) {
+                let contents = contents.trim();
+                return match contents.strip_prefix("ref:") {
+                    Some(target) => self.read_ref(target.trim(), depth + 1),
+                    None => Ok(Oid::from_hex(contents)),
+                };
+            }
+        }
+        self.packed_ref(name)
+    }
+
+    /// Looks a ref up in `packed-refs`, where `git gc` moves them.
+    fn packed_ref(&self, name: &str) -> io::Result<Option<Oid>> {
+        let packed = match fs::read_to_string(self.common_dir.join("packed-refs")) {
+            Ok(packed) => packed,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // Lines starting with `^` are the peeled value of the tag above.
+        let found = packed
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| line.split_once(' '))
+            .find(|(_, refname)| *refname == name);
+        Ok(found.and_then(|(hex, _)| Oid::from_hex(hex)))
+    }
+
+    /// The one object whose id starts with `prefix`, if there is one.
+    fn find_abbreviated(&self, prefix: &str) -> io::Result<Option<Oid>> {
+        if prefix.len() < 4 || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(None);
+        }
+        let prefix = prefix.to_ascii_lowercase();
+        let mut found = Vec::new();
+        for dir in &self.object_dirs {
+            let Ok(entries) = fs::read_dir(dir.join(&prefix[..2])) else {
+                continue;
+            };
+            for entry in entries {
+                let hex = format!("{}{}", &prefix[..2], entry?.file_name().to_string_lossy());
+                if hex.starts_with(&prefix) {
+                    found.extend(Oid::from_hex(&hex));
+                }
+            }
+        }
+        for pack in &self.packs {
+            found.extend(pack.ids().filter(|id| id.to_string().starts_with(&prefix)));
+        }
+        found.sort_unstable();
+        found.dedup();
+        match found[..] {
+            [] => Ok(None),
+            [oid] => Ok(Some(oid)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is ambiguous", prefix),
+            )),
+        }
+    }
+
+    /// The `n`th parent of a commit, counting from 1.
+    fn parent(&self, oid: Oid, n: usize) -> io::Result<Option<Oid>> {
+        let commit = self.read(self.peel(oid, Kind::Commit)?)?;
+        let parent = header_ids(&commit.data, "parent").nth(n - 1);
+        Ok(parent)
+    }
+
+    /// Follows tags, and commits to their trees, until it gets to an object
+    /// of kind `want`.
+    fn peel(&self, mut oid: Oid, want: Kind) -> io::Result<Oid> {
+        loop {
+            let object = self.read(oid)?;
+            if object.kind == want {
+                return Ok(oid);
+            }
+            let field = match object.kind {
+                Kind::Tag => "object",
+                Kind::Commit if want == Kind::Tree => "tree",
+                kind => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is a {}, not a {}", oid, kind, want),
+                    ))
+                }
+            };
+            oid = header_ids(&object.data, field)
+                .next()
+                .ok_or_else(|| corrupt(format!("{} {} has no {}", object.kind, oid, field)))?;
+        }
+    }
+
+    /// The tree of a commit, or of the commit a tag points at.
+    pub fn tree(&self, oid: Oid) -> io::Result<Oid> {
+        self.peel(oid, Kind::Tree)
+    }
+
+    /// Every file in a tree, with its path from the top of the tree and the
+    /// id of its contents, in the order git sorts them. Symlinks and
+    /// submodules are left out.
+    pub fn files(&self, tree: Oid) -> io::Result<Vec<(String, Oid)>> {
+        let mut files = Vec::new();
+        self.collect_files(tree, "", &mut files)?;
+        Ok(files)
+    }
+
+    fn collect_files(
+        &self,
+        tree: Oid,
+        prefix: &str,
+        files: &mut Vec<(String, Oid)>,
+    ) -> io::Result<()> {
+        let object = self.read(tree)?;
+        if object.kind != Kind::Tree {
+            return Err(corrupt(format!(
+                "{} is a {}, not a tree",
+                tree, object.kind
+            )));
+        }
+        let bad = || corrupt(format!("tree {} is broken", tree));
+        // Each entry is `MODE NAME\0` and then the id as 20 raw bytes.
+        let mut rest = &object.data[..];
+        while !rest.is_empty() {
+            let space = rest.iter().position(|&b| b == b' ').ok_or_else(bad)?;
+            let nul = rest.iter().position(|&b| b == 0).ok_or_else(bad)?;
+            let (mode, name) = (&rest[..space], rest.get(space + 1..nul).ok_or_else(bad)?);
+            let oid = rest.get(nul + 1..nul + 21).and_then(Oid::from_bytes);
+            let oid = oid.ok_or_else(bad)?;
+            rest = &rest[nul + 21..];
+
+            let path = format!("{}{}", prefix, String::from_utf8_lossy(name));
+            match mode {
+                b"40000" => self.collect_files(oid, &format!("{}/", path), files)?,
+                b"120000" | b"160000" => {}
+                _ => files.push((path, oid)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads an object, from a pack if it's in one, otherwise from its own
+    /// file.
+    pub fn read(&self, oid: Oid) -> io::Result<Object> {
+        self.read_at_depth(oid, 0)
+    }
+
+    /// Reads an object that's `depth` deltas away from the one asked for.
+    fn read_at_depth(&self, oid: Oid, depth: usize) -> io::Result<Object> {
+        for (i, pack) in self.packs.iter().enumerate() {
+            if let Some(offset) = pack.offset(oid) {
+                return self.read_packed(i, offset, depth);
+            }
+        }
+        let hex = oid.to_string();
+        for dir in &self.object_dirs {
+            match File::open(dir.join(&hex[..2]).join(&hex[2..])) {
+                Ok(file) => return read_loose(file),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("object {} is missing", oid),
+        ))
+    }
+
+    /// Reads the object at `offset` in a pack, rebuilding it from its base if
+    /// it's stored as a delta.
+    fn read_packed(&self, pack: usize, offset: u64, depth: usize) -> io::Result<Object> {
+        let data = &self.packs[pack].data[..];
+        let bad = || corrupt(format!("pack entry at {} is broken", offset));
+        if depth > MAX_DELTA_DEPTH {
+            return Err(corrupt(format!(
+                "pack entry at {} is more than {} deltas deep",
+                offset, MAX_DELTA_DEPTH
+            )));
+        }
+        let mut at = usize::try_from(offset).map_err(|_| bad())?;
+        let mut next = || -> io::Result<u8> {
+            let byte = data.get(at).copied().ok_or_else(bad)?;
+            at += 1;
+            Ok(byte)
+        };
+
+        // A type in bits 4-6 of the first byte, then the size in the low bits
+        // of that and the following bytes, least significant first.
+        let mut byte = next()?;
+        let number = (byte >> 4) & 7;
+        let mut size = (byte & 0x0f) as u64;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = next()?;
+            size |= ((byte & 0x7f) as u64).checked_shl(shift).ok_or_else(bad)?;
+            shift += 7;
+        }
+        let size = usize::try_from(size).map_err(|_| bad())?;
+
+        match number {
+            6 => {
+                // The base is earlier in this pack, a big-endian distance back
+                // where every continuation byte also adds one.
+                let mut byte = next()?;
+                let mut back = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = next()?;
+                    back = back
+                        .checked_add(1)
+                        .and_then(|b| b.checked_mul(128))
+                        .ok_or_else(bad)?
+                        | (byte & 0x7f) as u64;
+                }
+                let base = offset.checked_sub(back).filter(|_| back > 0);
+                let base = self.base(pack, base.ok_or_else(bad)?, depth + 1)?;
+                let delta = inflate(&data[at..], size)?;
+                Ok(Object {
+                    kind: base.kind,
+                    data: apply_delta(&base.data, &delta)?,
+                })
+            }
+            7 => {
+                // The base is named by its id and could be anywhere.
+                let base = data.get(at..at + 20).and_then(Oid::from_bytes);
+                let base = self.read_at_depth(base.ok_or_else(bad)?, depth + 1)?;
+                let delta = inflate(&data[at + 20..], size)?;
+                Ok(Object {
+                    kind: base.kind,
+                    data: apply_delta(&base.data, &delta)?,
+                })
+            }
+            number => Ok(Object {
+                kind: Kind::from_pack_type(number).ok_or_else(bad)?,
+                data: inflate(&data[at..], size)?,
+            }),
+        }
+    }
+
+    /// Reads a delta base, which neighbouring objects in a pack tend to
+    /// share, so they're cached.
+    fn base(&self, pack: usize, offset: u64, depth: usize) -> io::Result<Object> {
+        if let Some(object) = self.bases.borrow().objects.get(&(pack, offset)) {
+            return Ok(object.clone());
+        }
+        let object = self.read_packed(pack, offset, depth)?;
+        let mut bases = self.bases.borrow_mut();
+        if bases.bytes + object.data.len() > BASE_CACHE_SIZE {
+            *bases = BaseCache::default();
+        }
+        bases.bytes += object.data.len();
+        bases.objects.insert((pack, offset), object.clone());
+        Ok(object)
+    }
+}
+
+/// Recently read delta bases. It's emptied whenever it gets too big.
+#[derive(Default)]
+struct BaseCache {
+    objects: HashMap<(usize, u64), Object>,
+    bytes: usize,
+}
+
+/// A packfile and its index.
+struct Pack {
+    /// The version 2 `.idx`: a fanout table counting ids by first byte, the
+    /// sorted ids, their CRCs, their offsets, then any offsets too big for 31
+    /// bits.
+    index: Vec<u8>,
+    count: usize,
+    data: Mmap,
+}
+
+/// Where the sorted ids start in a pack index, after the header and fanout.
+const IDS: usize = 8 + 256 * 4;
+
+impl Pack {
+    fn open(index_path: &Path) -> io::Result<Pack> {
+        let index = fs::read(index_path)?;
+        if index.len() < IDS || index[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return Err(corrupt(format!(
+                "{} isn't a version 2 pack index",
+                index_path.display()
+            )));
+        }
+        let count = be32(&index, IDS - 4) as usize;
+        if index.len() < IDS + count * 28 {
+            return Err(corrupt(format!("{} is truncated", index_path.display())));
+        }
+
+        let file = File::open(index_path.with_extension("pack"))?;
+        // SAFETY: the map is only ever read. Packs are never modified in
+        // place, only replaced, so this has the same SIGBUS trade-off as
+        // mapping files to search them.
+        let data = unsafe { Mmap::map(&file)? };
+        if !data.starts_with(b"PACK") {
+            return Err(corrupt(format!("{} isn't a pack", index_path.display())));
+        }
+        Ok(Pack { index, count, data })
+    }
+
+    fn id(&self, i: usize) -> Oid {
+        let at = IDS + i * 20;
+        Oid::from_bytes(&self.index[at..at + 20]).unwrap()
+    }
+
+    fn ids(&self) -> impl Iterator<Item = Oid> + '_ {
+        (0..self.count).map(|i| self.id(i))
+    }
+
+    /// Where an object is in the pack, if it's there at all.
+    fn offset(&self, oid: Oid) -> Option<u64> {
+        let first = oid.0[0] as usize;
+        let mut lo = match first {
+            0 => 0,
+            _ => be32(&self.index, 8 + (first - 1) * 4) as usize,
+        };
+        let mut hi = (be32(&self.index, 8 + first * 4) as usize).min(self.count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.id(mid).cmp(&oid) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return self.offset_at(mid),
+            }
+        }
+        None
+    }
+
+    fn offset_at(&self, i: usize) -> Option<u64> {
+        let offsets = IDS + self.count * 24;
+        let offset = be32(&self.index, offsets + i * 4);
+        if offset & 0x8000_0000 == 0 {
+            return Some(offset as u64);
+        }
+        // Offsets past 2 GiB point into a table of 64-bit ones.
+        let at = offsets + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+        let bytes = self.index.get(at..at + 8)?;
+        Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+fn be32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn corrupt(reason: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.into())
+}
+
+/// The ids in the header lines of a commit or tag that start with `field`,
+/// e.g. a commit's parents.
+fn header_ids<'a>(data: &'a [u8], field: &'a str) -> impl Iterator<Item = Oid> + 'a {
+    data.split(|&b| b == b'\n')
+        .take_while(|line| !line.is_empty())
+        .filter_map(move |line| {
+            let value = line.strip_prefix(field.as_bytes())?.strip_prefix(b" ")?;
+            Oid::from_hex(std::str::from_utf8(value).ok()?)
+        })
+}
+
+/// Reads a loose object, which is zlib-compressed `KIND SIZE\0` followed by
+/// the contents.
+fn read_loose(file: File) -> io::Result<Object> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(file).read_to_end(&mut data)?;
+    let bad = || corrupt("loose object has a broken header");
+    let nul = data.iter().position(|&b| b == 0).ok_or_else(bad)?;
+    let header = std::str::from_utf8(&data[..nul]).map_err(|_| bad())?;
+    let (kind, size) = header.split_once(' ').ok_or_else(bad)?;
+    let kind = Kind::from_name(kind.as_bytes()).ok_or_else(bad)?;
+    let size: usize = size.parse().map_err(|_| bad())?;
+    data.drain(..=nul);
+    if data.len() != size {
+        return Err(bad());
+    }
+    Ok(Object { kind, data })
+}
+
+/// Inflates the zlib stream at the start of `compressed`, which should come
+/// out as exactly `size` bytes.
+fn inflate(compressed: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(size as u64 + 1)
+        .read_to_end(&mut data)?;
+    if data.len() != size {
+        return Err(corrupt("pack entry isn't the size it says it is"));
+    }
+    Ok(data)
+}
+
+/// Rebuilds an object from its base and a delta, which is the two sizes and
+/// then instructions to either copy part of the base or insert new bytes.
+fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+    let bad = || corrupt("pack has a broken delta");
+    let mut at = 0;
+    let base_size = varint(delta, &mut at).ok_or_else(bad)?;
+    let size = varint(delta, &mut at).ok_or_else(bad)?;
+    if base_size != base.len() {
+        return Err(bad());
+    }
+
+    // The size comes from the pack, so it isn't trusted with an allocation.
+    // Every copy is of at most 64K, which is plenty to start with.
+    let mut data = Vec::with_capacity(size.min(base.len() + delta.len()));
+    while let Some(&op) = delta.get(at) {
+        at += 1;
+        if op & 0x80 != 0 {
+            // The low seven bits say which bytes of the offset and length
+            // follow. Those left out are zero.
+            let mut field = |bits: std::ops::Range<u8>| -> io::Result<usize> {
+                let mut n = 0;
+                for (i, bit) in bits.enumerate() {
+                    if op & (1 << bit) != 0 {
+                        n |= (*delta.get(at).ok_or_else(bad)? as usize) << (8 * i);
+                        at += 1;
+                    }
+                }
+                Ok(n)
+            };
+            let offset = field(0..4)?;
+            let len = match field(4..7)? {
+                0 => 0x10000,
+                len => len,
+            };
+            data.extend_from_slice(base.get(offset..offset + len).ok_or_else(bad)?);
+        } else if op != 0 {
+            let len = op as usize;
+            data.extend_from_slice(delta.get(at..at + len).ok_or_else(bad)?);
+            at += len;
+        } else {
+            return Err(bad());
+        }
+        if data.len() > size {
+            return Err(bad());
+        }
+    }
+    if data.len() != size {
+        return Err(bad());
+    }
+    Ok(data)
+}
+
+/// Reads a size from a delta: seven bits a byte, least significant first.
+fn varint(bytes: &[u8], at: &mut usize) -> Option<usize> {
+    let mut n = 0usize;
+    let mut shift = 0u32;
+    loop {
+        let byte = *bytes.get(*at)?;
+        *at += 1;
+        n |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args([
+                "-c",
+                "user.name=Minigrep",
+                "-c",
+                "user.email=minigrep@example.com",
+            ])
+            .args(["-c", "commit.gpgsign=false", "-c", "tag.gpgsign=false"])
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    /// A repository with a tagged commit and one after it that changes a
+    /// line of a long file, so packing it makes a delta.
+    fn repo(name: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        git(&dir, &["init", "-q"]);
+
+        let lines: Vec<_> = (0..200)
+            .map(|i| format!("line {} of the poem", i))
+            .collect();
+        fs::write(dir.join("poem.txt"), lines.join("\n")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-qm", "first"]);
+        git(&dir, &["tag", "-a", "v1", "-m", "version 1"]);
+
+        fs::write(
+            dir.join("poem.txt"),
+            lines.join("\n").replace("line 7 ", "LINE 7 "),
+        )
+        .unwrap();
+        git(&dir, &["commit", "-qam", "second"]);
+        dir
+    }
+
+    fn contents(repo: &Repository, rev: &str) -> Vec<(String, String)> {
+        let tree = repo.tree(repo.resolve(rev).unwrap()).unwrap();
+        let files = repo.files(tree).unwrap();
+        let read = |(path, id)| {
+            (
+                path,
+                String::from_utf8(repo.read(id).unwrap().data).unwrap(),
+            )
+        };
+        files.into_iter().map(read).collect()
+    }
+
+    fn check_revisions(dir: &Path) {
+        let repo = Repository::discover(dir.join("src")).unwrap();
+        assert_eq!(fs::canonicalize(dir).unwrap(), repo.root());
+
+        let old = contents(&repo, "v1");
+        let paths: Vec<_> = old.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(vec!["poem.txt", "src/main.rs"], paths);
+        assert!(old[0].1.contains("line 7 of"));
+        assert_eq!(old, contents(&repo, "HEAD~1"));
+        assert_eq!(old, contents(&repo, "main^"));
+        assert!(contents(&repo, "HEAD")[0].1.contains("LINE 7 of"));
+
+        let head = git(dir, &["rev-parse", "HEAD"]);
+        assert_eq!(head, repo.resolve(&head[..7]).unwrap().to_string());
+        assert_eq!(head, repo.resolve("refs/heads/main").unwrap().to_string());
+        assert!(repo.resolve("nope").is_err());
+        assert!(repo.resolve("v1~5").is_err());
+    }
+
+    #[test]
+    fn reads_loose_objects() {
+        let dir = repo("git-loose");
+        git(&dir, &["branch", "-M", "main"]);
+        check_revisions(&dir);
+    }
+
+    #[test]
+    fn reads_packed_objects_and_refs() {
+        let dir = repo("git-packed");
+        git(&dir, &["branch", "-M", "main"]);
+        git(&dir, &["gc", "-q"]);
+        assert!(!dir.join(".git/refs/tags/v1").exists());
+        check_revisions(&dir);
+    }
+
+    #[test]
+    fn applies_deltas() {
+        let base = b"hello, world";
+        // The two sizes, copy 7 bytes from the start, then insert "rust".
+        let delta = [12, 11, 0x90, 7, 4, b'r', b'u', b's', b't'];
+        assert_eq!(b"hello, rust".to_vec(), apply_delta(base, &delta).unwrap());
+        assert!(apply_delta(b"short", &delta).is_err());
+
+        // A result size far too big to allocate, then a copy past it.
+        let mut huge = vec![12, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x40];
+        huge.extend([0x90, 7]);
+        assert!(apply_delta(base, &huge).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Shell-style globs for picking out files by name.
+//!
+//! Supports `*`, `?`, `[abc]`, `[a-z]`, `[!abc]`, `{a,b}` and `**`. A glob
+//! without a `/` is matched against the file name alone, so `*.rs` finds Rust
+//! files in any directory. A glob with a `/` is matched against the whole
+//! path, where `*` stops at `/` and `**` doesn't.
+//!
+//! Globs are compiled to a regex, so matching takes time in proportion to the
+//! length of the path however many stars there are.
+use std::fmt;
+use std::path::Path;
+
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `**` on its own
+    DoubleStar,
+    /// `**/`, which also matches nothing at all
+    AnyDirs,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// A compiled glob.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::glob::Glob;
+///
+/// let glob = Glob::new("*.{md,txt}").unwrap();
+/// assert!(glob.is_match("docs/README.md"));
+/// assert!(!glob.is_match("src/lib.rs"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    /// Every alternative once `{a,b}` has been expanded, as one regex.
+    regex: Regex,
+    whole_path: bool,
+}
+
+impl PartialEq for Glob {
+    fn eq(&self, other: &Glob) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+/// A glob that couldn't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobError {
+    pub pattern: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid glob {:?}: {}", self.pattern, self.reason)
+    }
+}
+
+impl std::error::Error for GlobError {}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+        let error = |reason| GlobError {
+            pattern: pattern.to_string(),
+            reason,
+        };
+        let alternatives = expand_braces(pattern)
+            .map_err(error)?
+            .iter()
+            .map(|alt| tokenize(alt).map(|tokens| to_regex(&tokens)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(error)?;
+        let regex = Regex::new(&format!(r"(?s)\A(?:{})\z", alternatives.join("|")))
+            .map_err(|_| error("too big"))?;
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            regex,
+            whole_path: pattern.contains('/'),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let text = if self.whole_path {
+            let path = path.strip_prefix(".").unwrap_or(path);
+            path.to_string_lossy()
+        } else {
+            match path.file_name() {
+                Some(name) => name.to_string_lossy(),
+                None => return false,
+            }
+        };
+        self.regex.is_match(&text)
+    }
+}
+
+/// A list of globs, where later ones override earlier ones and a leading `!`
+/// excludes instead of includes.
+///
+/// With no globs at all everything matches. With only `!` globs, everything
+/// they don't exclude matches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlobSet {
+    globs: Vec<(Glob, bool)>,
+}
+
+impl GlobSet {
+    pub fn new() -> GlobSet {
+        GlobSet::default()
+    }
+
+    /// Adds a glob. Prefix it with `!` to exclude matching files.
+    pub fn add(&mut self, pattern: &str) -> Result<(), GlobError> {
+        let (pattern, exclude) = match pattern.strip_prefix('!') {
+            Some(rest) => (rest, true),
+            None => (pattern, false),
+        };
+        self.globs.push((Glob::new(pattern)?, exclude));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let mut matched = self.globs.iter().all(|(_, exclude)| *exclude);
+        for (glob, exclude) in &self.globs {
+            if glob.is_match(path) {
+                matched = !exclude;
+            }
+        }
+        matched
+    }
+}
+
+/// Expands `{a,b}` alternatives, so `x{a,b{c,d}}` gives `xa`, `xbc` and `xbd`.
+fn expand_braces(pattern: &str) -> Result<Vec<String>, &'static str> {
+    let Some(open) = pattern.find('{') else {
+        if pattern.contains('}') {
+            return Err("unmatched }");
+        }
+        return Ok(vec![pattern.to_string()]);
+    };
+
+    let mut depth = 0;
+    let mut parts = Vec::new();
+    let mut part_start = open + 1;
+    for (i, c) in pattern[open..].char_indices().map(|(i, c)| (open + i, c)) {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 1 => {
+                parts.push(&pattern[part_start..i]);
+                let (prefix, suffix) = (&pattern[..open], &pattern[i + 1..]);
+                let mut expanded = Vec::new();
+                for part in parts {
+                    expanded.extend(expand_braces(&format!("{}{}{}", prefix, part, suffix))?);
+                }
+                return Ok(expanded);
+            }
+            '}' => depth -= 1,
+            ',' if depth == 1 => {
+                parts.push(&pattern[part_start..i]);
+                part_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    Err("unmatched {")
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '?' => Token::Any,
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    Token::AnyDirs
+                } else {
+                    Token::DoubleStar
+                }
+            }
+            '*' => Token::Star,
+            '[' => {
+                let negated = matches!(chars.peek(), Some('!') | Some('^'));
+                if negated {
+                    chars.next();
+                }
+                let mut ranges = Vec::new();
+                let mut first = true;
+                loop {
+                    let c = chars.next().ok_or("unmatched [")?;
+                    if c == ']' && !first {
+                        break;
+                    }
+                    first = false;
+                    let mut lookahead = chars.clone();
+                    if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|&e| e != ']')
+                    {
+                        chars.next();
+                        let end = chars.next().ok_or("unmatched [")?;
+                        if end < c {
+                            return Err("range out of order");
+                        }
+                        ranges.push((c, end));
+                    } else {
+                        ranges.push((c, c));
+                    }
+                }
+                Token::Class { negated, ranges }
+            }
+            '\\' => Token::Char(chars.next().ok_or("dangling \\")?),
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// The regex for one alternative, without anchors.
+fn to_regex(tokens: &[Token]) -> String {
+    let mut regex = String::new();
+    for token in tokens {
+        match token {
+            Token::Char(c) => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            Token::Any => regex.push_str("[^/]"),
+            Token::Star => regex.push_str("[^/]*"),
+            Token::DoubleStar => regex.push_str(".*"),
+            Token::AnyDirs => regex.push_str("(?:.*/)?"),
+            Token::Class { negated, ranges } => {
+                regex.push_str(if *negated { "[^/" } else { "[[" });
+                for &(lo, hi) in ranges {
+                    regex.push_str(&regex::escape(lo.encode_utf8(&mut [0; 4])));
+                    if hi != lo {
+                        regex.push('-');
+                        regex.push_str(&regex::escape(hi.encode_utf8(&mut [0; 4])));
+                    }
+                }
+                regex.push_str(if *negated { "]" } else { "]&&[^/]]" });
+            }
+        }
+    }
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_stay_within_a_directory() {
+        let glob = Glob::new("src/*.rs").unwrap();
+        assert!(glob.is_match("src/lib.rs"));
+        assert!(glob.is_match("./src/lib.rs"));
+        assert!(!glob.is_match("src/bin/main.rs"));
+
+        let glob = Glob::new("src/**/*.rs").unwrap();
+        assert!(glob.is_match("src/lib.rs"));
+        assert!(glob.is_match("src/bin/main.rs"));
+    }
+
+    #[test]
+    fn classes_and_braces() {
+        let glob = Glob::new("report-[0-9][!a-z].{csv,tsv}").unwrap();
+        assert!(glob.is_match("out/report-1A.csv"));
+        assert!(glob.is_match("report-22.tsv"));
+        assert!(!glob.is_match("report-2a.csv"));
+        assert!(!glob.is_match("report-22.json"));
+
+        assert!(Glob::new("{a,b").is_err());
+        assert!(Glob::new("[ab").is_err());
+        assert!(Glob::new("[z-a]").is_err());
+    }
+
+    #[test]
+    fn many_stars_dont_backtrack_forever() {
+        let name = format!("{}.txt", "a".repeat(40));
+        assert!(!Glob::new("*a*a*a*a*a*a*a*a*b").unwrap().is_match(&name));
+        assert!(Glob::new("*a*a*a*a*a*a*a*a*.txt").unwrap().is_match(&name));
+
+        let path = format!("{}/{}", ["a"; 20].join("/"), name);
+        assert!(!Glob::new("**a**a**a**a**a**a**a**a**/b")
+            .unwrap()
+            .is_match(&path));
+        assert!(Glob::new("a/**/a/**/*.txt").unwrap().is_match(&path));
+    }
+
+    #[test]
+    fn later_globs_win_and_bang_excludes() {
+        let mut set = GlobSet::new();
+        assert!(set.is_match("anything"));
+
+        set.add("*.log").unwrap();
+        set.add("!debug.log").unwrap();
+        assert!(set.is_match("logs/app.log"));
+        assert!(!set.is_match("logs/debug.log"));
+        assert!(!set.is_match("notes.txt"));
+
+        let mut set = GlobSet::new();
+        set.add("!*.min.js").unwrap();
+        assert!(set.is_match("app.js"));
+        assert!(!set.is_match("app.min.js"));
+    }
+}
@@ -0,0 +1,487 @@
+//! An on-disk trigram index, so repeated searches over a big tree only have
+//! to open the files that could possibly match.
+//!
+//! `minigrep index build DIR` records, for every file under `DIR`, the set of
+//! three-byte sequences (trigrams) its text contains. A file can only contain
+//! the query if it contains every trigram of the query, so `minigrep --index`
+//! skips any file that's missing one. The index is only ever used to narrow
+//! down which files get searched, never to decide what matches, so results are
+//! always the same as without it.
+//!
+//! Trigrams are taken from the text after lowercasing each character, which
+//! means the same index works for case-sensitive and case-insensitive
+//! searches. Entries remember the size and modification time of their file
+//! and are rebuilt whenever either changes.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::input::{self, MmapChoice};
+use crate::walk::Walk;
+
+/// Name of the index file written at the root of an indexed directory.
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+const MAGIC: &[u8] = b"minigrep-index 1\n";
+
+pub type Trigram = [u8; 3];
+
+/// Returns the sorted, de-duplicated trigrams of `text` once lowercased.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::index::trigrams;
+///
+/// assert_eq!(vec![*b"bob", *b"obo"], trigrams("BoBo"));
+/// ```
+pub fn trigrams(text: &str) -> Vec<Trigram> {
+    let folded: String = text.chars().flat_map(char::to_lowercase).collect();
+    let mut trigrams: Vec<Trigram> = folded
+        .as_bytes()
+        .windows(3)
+        .map(|w| [w[0], w[1], w[2]])
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    modified: (u64, u32),
+    len: u64,
+    /// `None` if the file couldn't be read as text. Such files are always
+    /// searched, so they fail the same way they would without the index.
+    trigrams: Option<Vec<Trigram>>,
+}
+
+impl Entry {
+    fn stamp(metadata: &fs::Metadata) -> ((u64, u32), u64) {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or((0, 0), |d| (d.as_secs(), d.subsec_nanos()));
+        (modified, metadata.len())
+    }
+
+    fn may_contain(&self, query: &[Trigram]) -> bool {
+        match &self.trigrams {
+            Some(trigrams) => query.iter().all(|t| trigrams.binary_search(t).is_ok()),
+            None => true,
+        }
+    }
+}
+
+/// How much work [`Index::build`] had to do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildStats {
+    pub files: usize,
+    pub reused: usize,
+    pub removed: usize,
+}
+
+/// The trigram index for one directory.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Index {
+    root: PathBuf,
+    /// Keyed by path relative to `root`.
+    files: BTreeMap<PathBuf, Entry>,
+    dirty: bool,
+}
+
+impl Index {
+    /// Loads the index stored in `root`, or starts an empty one if there
+    /// isn't one yet.
+    pub fn load(root: impl AsRef<Path>) -> io::Result<Index> {
+        let root = root.as_ref().to_path_buf();
+        let bytes = match fs::read(root.join(INDEX_FILE)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Index {
+                    root,
+                    ..Index::default()
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        let files = decode(&bytes).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is corrupt, rebuild it", root.join(INDEX_FILE).display()),
+            )
+        })?;
+        Ok(Index {
+            root,
+            files,
+            dirty: false,
+        })
+    }
+
+    /// Writes the index back to disk, if anything changed since it was loaded.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        // Write to a temporary file first so a crash can't leave half an index.
+        let path = self.root.join(INDEX_FILE);
+        let temp = self.root.join(format!("{}.tmp", INDEX_FILE));
+        fs::write(&temp, encode(&self.files))?;
+        fs::rename(temp, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Creates or updates the index for `root` and saves it.
+    ///
+    /// Files whose size and modification time haven't changed keep their
+    /// existing entries.
+    pub fn build(root: impl AsRef<Path>) -> io::Result<BuildStats> {
+        let mut index = Index::load(root)?;
+        let mut stats = BuildStats::default();
+        let mut seen = Vec::new();
+
+        for file in Walk::new([&index.root]) {
+            let Ok(file) = file else { continue };
+            let Ok(rel) = file.strip_prefix(&index.root).map(Path::to_path_buf) else {
+                continue;
+            };
+            if index.refresh(&rel) {
+                stats.reused += 1;
+            }
+            stats.files += 1;
+            seen.push(rel);
+        }
+
+        let before = index.files.len();
+        seen.sort();
+        index
+            .files
+            .retain(|path, _| seen.binary_search(path).is_ok());
+        stats.removed = before - index.files.len();
+        if stats.removed > 0 {
+            index.dirty = true;
+        }
+
+        index.save()?;
+        Ok(stats)
+    }
+
+    /// Returns false if the file at `rel` (relative to the root) certainly
+    /// doesn't contain text with all of the `query` trigrams.
+    ///
+    /// Entries that are missing or out of date are rebuilt first.
+    pub fn may_contain(&mut self, rel: &Path, query: &[Trigram]) -> bool {
+        self.refresh(rel);
+        self.files
+            .get(rel)
+            .is_none_or(|entry| entry.may_contain(query))
+    }
+
+    /// Brings the entry for `rel` up to date. Returns true if the existing
+    /// entry could be reused as it was.
+    fn refresh(&mut self, rel: &Path) -> bool {
+        let path = self.root.join(rel);
+        let Ok(metadata) = fs::metadata(&path) else {
+            self.dirty |= self.files.remove(rel).is_some();
+            return false;
+        };
+        let (modified, len) = Entry::stamp(&metadata);
+        if let Some(entry) = self.files.get(rel) {
+            if entry.modified == modified && entry.len == len {
+                return true;
+            }
+        }
+
+        let trigrams = input::read(&path, MmapChoice::Auto)
+            .ok()
+            .map(|contents| trigrams(&contents));
+        self.files.insert(
+            rel.to_path_buf(),
+            Entry {
+                modified,
+                len,
+                trigrams,
+            },
+        );
+        self.dirty = true;
+        false
+    }
+}
+
+/// Finds the nearest directory at or above `path` that has an index in it.
+pub fn find_root(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok()?;
+    path.ancestors()
+        .find(|dir| dir.join(INDEX_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Decides which files `minigrep --index` needs to search.
+pub struct Narrower {
+    query: Vec<Trigram>,
+    /// For each search path: the path as given, where it sits relative to
+    /// its index root, and which index that is.
+    roots: Vec<(PathBuf, PathBuf, usize)>,
+    indexes: Vec<Index>,
+}
+
+impl Narrower {
+    /// Loads the indexes covering `paths`. Paths that aren't inside an
+    /// indexed directory are searched in full.
+    pub fn new<P: AsRef<Path>>(paths: &[P], query: &str) -> io::Result<Narrower> {
+        let mut roots = Vec::new();
+        let mut indexes: Vec<Index> = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let Some(root) = find_root(path) else {
+                continue;
+            };
+            let Ok(rel) = fs::canonicalize(path)?
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+            else {
+                continue;
+            };
+            let i = match indexes.iter().position(|index| index.root == root) {
+                Some(i) => i,
+                None => {
+                    indexes.push(Index::load(&root)?);
+                    indexes.len() - 1
+                }
+            };
+            roots.push((path.to_path_buf(), rel, i));
+        }
+        Ok(Narrower {
+            query: trigrams(query),
+            roots,
+            indexes,
+        })
+    }
+
+    /// Returns false if `file` can be skipped because it can't match.
+    pub fn keep(&mut self, file: &Path) -> bool {
+        if self.query.is_empty() {
+            return true;
+        }
+        let Some((path, rel, i)) = self.roots.iter().find(|(path, ..)| file.starts_with(path))
+        else {
+            return true;
+        };
+        let rel = match file.strip_prefix(path) {
+            Ok(rest) if !rest.as_os_str().is_empty() => rel.join(rest),
+            _ => rel.clone(),
+        };
+        self.indexes[*i].may_contain(&rel, &self.query)
+    }
+
+    /// Saves any entries that had to be rebuilt along the way.
+    pub fn save(&mut self) -> io::Result<()> {
+        self.indexes.iter_mut().try_for_each(Index::save)
+    }
+}
+
+/// The directory in `minigrep index build DIR`, if that's what `args`, the
+/// whole command line, asks for.
+///
+/// It only counts as the command when `DIR` is a directory and there's
+/// nothing called `build` here. Otherwise it's a search for "index" like any
+/// other, so `minigrep index build notes.txt` still looks in `build` and
+/// `notes.txt`.
+pub fn build_command(args: &[String]) -> Option<&str> {
+    match args {
+        [_, command, build, dir]
+            if command == "index"
+                && build == "build"
+                && Path::new(dir).is_dir()
+                && !Path::new(build).exists() =>
+        {
+            Some(dir)
+        }
+        _ => None,
+    }
+}
+
+/// Builds or updates the index in `dir`, for `minigrep index build DIR`.
+pub fn command(dir: &str) -> Result<(), Box<dyn Error>> {
+    let stats = Index::build(dir)?;
+    println!(
+        "Indexed {} files in {} ({} unchanged, {} removed)",
+        stats.files, dir, stats.reused, stats.removed
+    );
+    Ok(())
+}
+
+fn encode(files: &BTreeMap<PathBuf, Entry>) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    let paths: Vec<_> = files
+        .iter()
+        .filter_map(|(path, entry)| Some((path.to_str()?, entry)))
+        .collect();
+    out.extend((paths.len() as u64).to_le_bytes());
+    for (path, entry) in paths {
+        out.extend((path.len() as u64).to_le_bytes());
+        out.extend(path.as_bytes());
+        out.extend(entry.modified.0.to_le_bytes());
+        out.extend(entry.modified.1.to_le_bytes());
+        out.extend(entry.len.to_le_bytes());
+        match &entry.trigrams {
+            Some(trigrams) => {
+                out.push(1);
+                out.extend((trigrams.len() as u64).to_le_bytes());
+                out.extend(trigrams.iter().flatten());
+            }
+            None => out.push(0),
+        }
+    }
+    out
+}
+
+fn decode(mut bytes: &[u8]) -> Option<BTreeMap<PathBuf, Entry>> {
+    fn take<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+        let mut buf = [0; N];
+        bytes.read_exact(&mut buf).ok()?;
+        Some(buf)
+    }
+    fn take_u64(bytes: &mut &[u8]) -> Option<u64> {
+        take(bytes).map(u64::from_le_bytes)
+    }
+    fn take_len(bytes: &mut &[u8], size: usize) -> Option<usize> {
+        let len = usize::try_from(take_u64(bytes)?).ok()?;
+        // Guard against corrupt lengths before allocating anything.
+        (len.checked_mul(size)? <= bytes.len()).then_some(len)
+    }
+
+    bytes = bytes.strip_prefix(MAGIC)?;
+    let count = take_len(&mut bytes, 1)?;
+    let mut files = BTreeMap::new();
+    for _ in 0..count {
+        let path_len = take_len(&mut bytes, 1)?;
+        let (path, rest) = bytes.split_at(path_len);
+        let path = PathBuf::from(std::str::from_utf8(path).ok()?);
+        bytes = rest;
+
+        let modified = (take_u64(&mut bytes)?, u32::from_le_bytes(take(&mut bytes)?));
+        let len = take_u64(&mut bytes)?;
+        let trigrams = match take::<1>(&mut bytes)? {
+            [1] => {
+                let n = take_len(&mut bytes, 3)?;
+                let mut trigrams = Vec::with_capacity(n);
+                for _ in 0..n {
+                    trigrams.push(take::<3>(&mut bytes)?);
+                }
+                Some(trigrams)
+            }
+            [0] => None,
+            _ => return None,
+        };
+        files.insert(
+            path,
+            Entry {
+                modified,
+                len,
+                trigrams,
+            },
+        );
+    }
+    bytes.is_empty().then_some(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    #[test]
+    fn entries_round_trip_through_the_file_format() {
+        let mut files = BTreeMap::new();
+        files.insert(
+            PathBuf::from("src/lib.rs"),
+            Entry {
+                modified: (1_700_000_000, 42),
+                len: 9,
+                trigrams: Some(trigrams("pub fn run")),
+            },
+        );
+        files.insert(
+            PathBuf::from("logo.png"),
+            Entry {
+                modified: (1, 2),
+                len: 3,
+                trigrams: None,
+            },
+        );
+
+        let bytes = encode(&files);
+        assert_eq!(Some(files), decode(&bytes));
+        assert_eq!(None, decode(&bytes[..bytes.len() - 1]));
+    }
+
+    #[test]
+    fn index_build_is_only_a_command_with_a_directory() {
+        let dir = temp_dir("index-command");
+        let dir = dir.to_str().unwrap();
+        let args = |rest: &[&str]| -> Vec<String> {
+            ["minigrep", "index", "build"]
+                .iter()
+                .chain(rest)
+                .map(|a| a.to_string())
+                .collect()
+        };
+        assert_eq!(Some(dir), build_command(&args(&[dir])));
+
+        for args in [args(&[]), args(&["Cargo.toml"]), args(&[dir, dir])] {
+            assert_eq!(None, build_command(&args));
+            let config = crate::Config::new(args.into_iter()).unwrap();
+            assert_eq!("index", config.query);
+            assert_eq!("build", config.paths[0]);
+        }
+    }
+
+    #[test]
+    fn narrows_to_files_with_every_query_trigram() {
+        let dir = temp_dir("index-narrow");
+        fs::write(dir.join("poem.txt"), "How public, like a frog").unwrap();
+        fs::write(dir.join("other.txt"), "Nothing to see here").unwrap();
+        let stats = Index::build(&dir).unwrap();
+        assert_eq!((2, 0), (stats.files, stats.reused));
+
+        let mut narrower = Narrower::new(&[&dir], "FROG").unwrap();
+        assert!(narrower.keep(&dir.join("poem.txt")));
+        assert!(!narrower.keep(&dir.join("other.txt")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rebuilding_only_reindexes_changed_files() {
+        let dir = temp_dir("index-rebuild");
+        fs::write(dir.join("same.txt"), "unchanged").unwrap();
+        fs::write(dir.join("edited.txt"), "before").unwrap();
+        fs::write(dir.join("gone.txt"), "deleted soon").unwrap();
+        Index::build(&dir).unwrap();
+
+        fs::write(dir.join("edited.txt"), "after the edit").unwrap();
+        fs::remove_file(dir.join("gone.txt")).unwrap();
+        let stats = Index::build(&dir).unwrap();
+        assert_eq!(
+            BuildStats {
+                files: 2,
+                reused: 1,
+                removed: 1
+            },
+            stats
+        );
+
+        // Edits made after the build are still found.
+        fs::write(dir.join("same.txt"), "now mentions a frog").unwrap();
+        let mut narrower = Narrower::new(&[dir.join("same.txt")], "frog").unwrap();
+        assert!(narrower.keep(&dir.join("same.txt")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+//! Reading files into memory so they can be searched.
+//!
+//! Small files are read into a `String` as usual. Big regular files are
+//! memory-mapped instead, which saves copying the whole thing onto the heap.
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+/// Files at least this big get memory-mapped when the choice is [`MmapChoice::Auto`].
+pub const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// Whether to memory-map files before searching them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MmapChoice {
+    /// Map regular files that are at least [`MMAP_THRESHOLD`] bytes.
+    #[default]
+    Auto,
+    /// Map every regular file that can be mapped.
+    Always,
+    /// Always read files into a `String`.
+    Never,
+}
+
+/// The contents of a file, either read onto the heap or mapped into memory.
+///
+/// Derefs to `str` either way.
+#[derive(Debug)]
+pub enum Contents {
+    Owned(String),
+    Mapped(Mmap),
+}
+
+impl Contents {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Contents::Owned(s) => s,
+            // SAFETY: `read` only builds a `Mapped` after checking the bytes
+            // are valid UTF-8.
+            Contents::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, Contents::Mapped(_))
+    }
+}
+
+impl Deref for Contents {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Reads the file at `path`, memory-mapping it if `choice` allows.
+///
+/// Anything that isn't a regular file with a known size (pipes, sockets,
+/// `/proc` entries that claim to be empty, ...) or that fails to map is read
+/// the ordinary way instead, so `MmapChoice::Always` never makes a read fail
+/// that would otherwise have worked.
+///
+/// Like `fs::read_to_string`, this fails if the file isn't valid UTF-8.
+pub fn read(path: impl AsRef<Path>, choice: MmapChoice) -> io::Result<Contents> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+    let metadata = file.metadata()?;
+
+    let wants_map = match choice {
+        MmapChoice::Never => false,
+        MmapChoice::Always => true,
+        MmapChoice::Auto => metadata.len() >= MMAP_THRESHOLD,
+    };
+    if wants_map && metadata.is_file() && metadata.len() > 0 {
+        // SAFETY: the map is only ever read. If another process truncates the
+        // file while we are searching it we can get a SIGBUS, which is the
+        // same trade-off every mmap-based grep makes.
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            return match std::str::from_utf8(&map) {
+                Ok(_) => Ok(Contents::Mapped(map)),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )),
+            };
+        }
+    }
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(Contents::Owned(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+    use std::fs;
+
+    fn temp_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn maps_only_when_asked_or_big_enough() {
+        let path = temp_file("small.txt", "Rust:\nsafe, fast, productive.");
+
+        let auto = read(&path, MmapChoice::Auto).unwrap();
+        let always = read(&path, MmapChoice::Always).unwrap();
+        assert!(!auto.is_mapped());
+        assert!(always.is_mapped());
+        assert_eq!(&*auto, &*always);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn mapped_files_must_be_utf8() {
+        let path = temp_path("binary");
+        fs::write(&path, [0xff, 0xfe, 0x00]).unwrap();
+
+        let err = read(&path, MmapChoice::Always).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn special_files_fall_back_to_reading() {
+        let contents = read("/proc/self/status", MmapChoice::Always).unwrap();
+
+        assert!(!contents.is_mapped());
+        assert!(contents.contains("Pid:"));
+    }
+}
@@ -0,0 +1,643 @@
+//! The full-screen `--interactive` mode.
+//!
+//! Every file is read into memory once up front, and each keystroke runs the
+//! search over them again, so refining a query is instant. The screen has the
+//! query at the top, the matching lines under it and a preview of the
+//! selected match's surroundings at the bottom. Enter opens the selected line
+//! in `$VISUAL` or `$EDITOR`.
+use std::env;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::printer::{HIGHLIGHT, RESET};
+use crate::Config;
+
+/// Matches past this many aren't collected, so a one letter query over a big
+/// tree stays responsive.
+const MAX_HITS: usize = 10_000;
+
+/// Reads the files, then runs the UI until the user quits.
+pub fn run(mut config: Config) -> Result<(), Box<dyn Error>> {
+    let mut app = App::load(&config);
+    app.search(&mut config);
+    let terminal = Terminal::open()?;
+    loop {
+        let (width, height) = terminal.size();
+        let layout = Layout::new(height);
+        app.scroll_to_selected(layout.list);
+        terminal.draw(&app.render(width, &layout))?;
+
+        for key in terminal.keys()? {
+            match key {
+                Key::Escape | Key::Ctrl('c') | Key::Ctrl('d') => return Ok(()),
+                Key::Enter => {
+                    let Some(hit) = app.hits.get(app.selected) else {
+                        continue;
+                    };
+                    let (file, line_number) = (hit.file, hit.line_number);
+                    terminal.leave()?;
+                    let edited = edit(&app.files[file].path, line_number, &terminal.tty);
+                    terminal.enter()?;
+                    match edited {
+                        Ok(()) => app.reload(file, &mut config),
+                        Err(e) => app.status = format!("couldn't open the editor: {}", e),
+                    }
+                }
+                key => app.handle(key, layout.list, &mut config),
+            }
+        }
+    }
+}
+
+/// A file read into memory.
+struct Loaded {
+    path: PathBuf,
+    contents: String,
+}
+
+/// A matching line.
+#[derive(Debug, Clone, PartialEq)]
+struct Hit {
+    /// Index into the loaded files.
+    file: usize,
+    line_number: usize,
+    /// Where the line is in the file.
+    line: Range<usize>,
+    /// The matches, relative to the start of the line.
+    ranges: Vec<Range<usize>>,
+}
+
+struct App {
+    files: Vec<Loaded>,
+    query: String,
+    hits: Vec<Hit>,
+    /// What's shown under the query: a count of hits, or what's wrong.
+    status: String,
+    /// How many files couldn't be walked to or read, so they aren't loaded.
+    unreadable: usize,
+    selected: usize,
+    /// The first hit shown in the list.
+    scroll: usize,
+}
+
+impl App {
+    /// Reads every file the config would search. Ones that can't be read are
+    /// skipped, and counted in the status line.
+    fn load(config: &Config) -> App {
+        let mut files = Vec::new();
+        let mut unreadable = 0;
+        for path in config.walk() {
+            let path = match path {
+                Ok(path) => path,
+                // Files over --max-filesize are left out quietly, like they
+                // are from a normal search.
+                Err(e) if e.is_too_large() => continue,
+                Err(_) => {
+                    unreadable += 1;
+                    continue;
+                }
+            };
+            match config.read(&path) {
+                Ok(contents) => files.push(Loaded {
+                    path,
+                    contents: contents.to_string(),
+                }),
+                Err(_) => unreadable += 1,
+            }
+        }
+        App {
+            files,
+            query: config.query.clone(),
+            hits: Vec::new(),
+            status: String::new(),
+            unreadable,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    /// Reads a file again after it's been edited, and searches again.
+    fn reload(&mut self, file: usize, config: &mut Config) {
+        let loaded = &mut self.files[file];
+        let read = config.read(&loaded.path);
+        if let Ok(contents) = &read {
+            loaded.contents = contents.to_string();
+        }
+        let selected = self.selected;
+        self.search(config);
+        self.selected = selected.min(self.hits.len().saturating_sub(1));
+        if let Err(e) = read {
+            self.status = format!("couldn't read {}: {}", self.files[file].path.display(), e);
+        }
+    }
+
+    /// Runs the current query over every file.
+    fn search(&mut self, config: &mut Config) {
+        self.hits.clear();
+        self.selected = 0;
+        self.scroll = 0;
+        if self.query.is_empty() {
+            self.status = format!(
+                "{} files loaded{}, type to search",
+                self.files.len(),
+                self.unreadable_note()
+            );
+            return;
+        }
+
+        config.query = self.query.clone();
+        let matcher = match config.matcher() {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                self.status = e.to_string();
+                return;
+            }
+        };
+        let searcher = config.searcher();
+        let mut matched_files = 0;
+        'files: for (i, file) in self.files.iter().enumerate() {
+            let mut matched = false;
+            for m in searcher.find_iter(&matcher, &file.contents) {
+                if self.hits.len() == MAX_HITS {
+                    break 'files;
+                }
+                matched = true;
+                self.hits.push(Hit {
+                    file: i,
+                    line_number: m.line_number,
+                    line: m.offset..m.offset + m.text.len(),
+                    ranges: m.ranges.clone(),
+                });
+            }
+            matched_files += usize::from(matched);
+        }
+        self.status = format!(
+            "{}{} matching lines in {} of {} files{}",
+            if self.hits.len() == MAX_HITS {
+                "the first "
+            } else {
+                ""
+            },
+            self.hits.len(),
+            matched_files,
+            self.files.len(),
+            self.unreadable_note()
+        );
+    }
+
+    fn unreadable_note(&self) -> String {
+        match self.unreadable {
+            0 => String::new(),
+            n => format!(" ({} couldn't be read)", n),
+        }
+    }
+
+    /// Handles a key that edits the query or moves the selection. `page` is
+    /// how many hits fit in the list.
+    fn handle(&mut self, key: Key, page: usize, config: &mut Config) {
+        let page = page.max(1) as isize;
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.search(config);
+            }
+            Key::Backspace if !self.query.is_empty() => {
+                self.query.pop();
+                self.search(config);
+            }
+            Key::Ctrl('u') => {
+                self.query.clear();
+                self.search(config);
+            }
+            Key::Up | Key::Ctrl('p') => self.select_by(-1),
+            Key::Down | Key::Ctrl('n') => self.select_by(1),
+            Key::PageUp => self.select_by(-page),
+            Key::PageDown => self.select_by(page),
+            _ => {}
+        }
+    }
+
+    fn select_by(&mut self, delta: isize) {
+        let last = self.hits.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Scrolls the list just enough that the selected hit is in it.
+    fn scroll_to_selected(&mut self, list_height: usize) {
+        let list_height = list_height.max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+    }
+
+    /// Draws the whole screen: the query, the status, the list of hits and
+    /// the preview.
+    fn render(&self, width: usize, layout: &Layout) -> String {
+        let mut rows = Vec::with_capacity(layout.height);
+        rows.push(format!(
+            "> {}",
+            fit(&self.query, &[], width.saturating_sub(2))
+        ));
+        rows.push(format!("\x1b[2m{}\x1b[0m", fit(&self.status, &[], width)));
+
+        for i in self.scroll..self.scroll + layout.list {
+            let Some(hit) = self.hits.get(i) else {
+                rows.push(String::new());
+                continue;
+            };
+            let file = &self.files[hit.file];
+            let marker = if i == self.selected {
+                "\x1b[7m>\x1b[0m "
+            } else {
+                "  "
+            };
+            let prefix = format!("{}:{}: ", file.path.display(), hit.line_number);
+            let text = format!("{}{}", prefix, &file.contents[hit.line.clone()]);
+            let ranges: Vec<_> = hit
+                .ranges
+                .iter()
+                .map(|r| r.start + prefix.len()..r.end + prefix.len())
+                .collect();
+            rows.push(format!(
+                "{}{}",
+                marker,
+                fit(&text, &ranges, width.saturating_sub(2))
+            ));
+        }
+
+        if layout.preview > 0 {
+            rows.extend(self.preview(width, layout.preview));
+        }
+        rows.iter()
+            .enumerate()
+            .map(|(i, row)| format!("\x1b[{};1H{}\x1b[K", i + 1, row))
+            .collect::<String>()
+            + &format!("\x1b[1;{}H", 3 + self.query.chars().count().min(width))
+    }
+
+    /// A rule with the selected file's name in it, then the lines around the
+    /// selected hit with the hit itself highlighted.
+    fn preview(&self, width: usize, height: usize) -> Vec<String> {
+        let Some(hit) = self.hits.get(self.selected) else {
+            return vec![format!("\x1b[2m{}\x1b[0m", "─".repeat(width))];
+        };
+        let file = &self.files[hit.file];
+        let title = format!("── {} ", file.path.display());
+        let rule =
+            fit(&title, &[], width) + &"─".repeat(width.saturating_sub(title.chars().count()));
+        let mut rows = vec![format!("\x1b[2m{}\x1b[0m", rule)];
+
+        let context = height.saturating_sub(1);
+        let first = hit.line_number.saturating_sub(context / 2).max(1);
+        for (i, line) in file
+            .contents
+            .lines()
+            .enumerate()
+            .skip(first - 1)
+            .take(context)
+        {
+            let number = i + 1;
+            let row = if number == hit.line_number {
+                format!(
+                    "\x1b[1m{:>6}\x1b[0m {}",
+                    number,
+                    fit(line, &hit.ranges, width.saturating_sub(7))
+                )
+            } else {
+                format!(
+                    "\x1b[2m{:>6}\x1b[0m {}",
+                    number,
+                    fit(line, &[], width.saturating_sub(7))
+                )
+            };
+            rows.push(row);
+        }
+        rows
+    }
+}
+
+/// How the rows of the screen are shared out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    height: usize,
+    /// Rows for the list of hits.
+    list: usize,
+    /// Rows for the preview, including its rule. Zero on tiny screens.
+    preview: usize,
+}
+
+impl Layout {
+    fn new(height: usize) -> Layout {
+        // The query and status lines come first.
+        let rest = height.saturating_sub(2);
+        if rest < 6 {
+            return Layout {
+                height,
+                list: rest,
+                preview: 0,
+            };
+        }
+        let list = rest / 2;
+        Layout {
+            height,
+            list,
+            preview: rest - list,
+        }
+    }
+}
+
+/// Cuts `text` down to `width` characters, highlighting `ranges` of it.
+/// Tabs and other control characters are shown as spaces.
+fn fit(text: &str, ranges: &[Range<usize>], width: usize) -> String {
+    let mut out = String::with_capacity(text.len().min(width * 4));
+    let mut highlighted = false;
+    for (shown, (i, c)) in text.char_indices().enumerate() {
+        if shown == width {
+            break;
+        }
+        let inside = ranges.iter().any(|r| r.contains(&i));
+        if inside != highlighted {
+            out.push_str(if inside { HIGHLIGHT } else { RESET });
+            highlighted = inside;
+        }
+        out.push(if c.is_control() { ' ' } else { c });
+    }
+    if highlighted {
+        out.push_str(RESET);
+    }
+    out
+}
+
+/// Opens `path` at `line_number` in the user's editor and waits for it.
+fn edit(path: &Path, line_number: usize, tty: &File) -> io::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor can come with arguments, like `code --wait`.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(format!("+{}", line_number))
+        .arg(path)
+        .stdin(tty.try_clone()?)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{} failed: {}", program, status)));
+    }
+    Ok(())
+}
+
+/// A key press, as far as the UI cares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    Ctrl(char),
+    Backspace,
+    Enter,
+    Escape,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+}
+
+/// Turns the bytes a terminal sends in raw mode into keys. Escape sequences
+/// that don't mean anything here are dropped.
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let key = match bytes[i] {
+            0x1b if matches!(bytes.get(i + 1), Some(b'[' | b'O')) => {
+                // A control sequence ends at its first byte in @ to ~.
+                let end = bytes[i + 2..]
+                    .iter()
+                    .position(|b| (0x40..=0x7e).contains(b))
+                    .map_or(bytes.len(), |p| i + 3 + p);
+                let key = match &bytes[i + 2..end] {
+                    b"A" => Some(Key::Up),
+                    b"B" => Some(Key::Down),
+                    b"5~" => Some(Key::PageUp),
+                    b"6~" => Some(Key::PageDown),
+                    _ => None,
+                };
+                i = end;
+                keys.extend(key);
+                continue;
+            }
+            0x1b => Key::Escape,
+            b'\r' | b'\n' => Key::Enter,
+            0x7f | 0x08 => Key::Backspace,
+            byte @ 1..=26 => Key::Ctrl((b'a' + byte - 1) as char),
+            byte if byte < 0x20 => {
+                i += 1;
+                continue;
+            }
+            byte => {
+                let len = match byte {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                };
+                let text = bytes
+                    .get(i..i + len)
+                    .and_then(|b| std::str::from_utf8(b).ok());
+                match text.and_then(|t| t.chars().next()) {
+                    Some(c) => {
+                        keys.push(Key::Char(c));
+                        i += len;
+                    }
+                    None => i += 1,
+                }
+                continue;
+            }
+        };
+        keys.push(key);
+        i += 1;
+    }
+    keys
+}
+
+/// The terminal in raw mode and on the alternate screen. It's put back the
+/// way it was when dropped.
+///
+/// Keys are read from `/dev/tty` rather than stdin, so stdin can be used for
+/// something else, like `--files-from -`.
+struct Terminal {
+    tty: File,
+    original: libc::termios,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        let no_terminal = || io::Error::other("--interactive needs a terminal");
+        // SAFETY: isatty only looks at the descriptor.
+        if unsafe { libc::isatty(1) == 0 } {
+            return Err(no_terminal());
+        }
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|_| no_terminal())?;
+        // SAFETY: termios is plain data, and tcgetattr fills it in.
+        let mut original = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let terminal = Terminal { tty, original };
+        terminal.enter()?;
+        Ok(terminal)
+    }
+
+    /// Switches to raw mode and the alternate screen.
+    fn enter(&self) -> io::Result<()> {
+        let mut raw = self.original;
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: `raw` is a valid termios copied from the real one.
+        if unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut out = io::stdout();
+        out.write_all(b"\x1b[?1049h")?;
+        out.flush()
+    }
+
+    /// Puts the terminal back, for the editor or for good.
+    fn leave(&self) -> io::Result<()> {
+        let mut out = io::stdout();
+        out.write_all(b"\x1b[?1049l")?;
+        out.flush()?;
+        // SAFETY: `original` came from tcgetattr.
+        if unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// The width and height in characters.
+    fn size(&self) -> (usize, usize) {
+        // SAFETY: winsize is plain data, and the ioctl fills it in.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            (size.ws_col as usize, size.ws_row as usize)
+        } else {
+            (80, 24)
+        }
+    }
+
+    fn draw(&self, screen: &str) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        out.write_all(screen.as_bytes())?;
+        out.flush()
+    }
+
+    /// Waits for input and returns the keys in it.
+    fn keys(&self) -> io::Result<Vec<Key>> {
+        let mut buffer = [0; 64];
+        let n = (&self.tty).read(&mut buffer)?;
+        Ok(parse_keys(&buffer[..n]))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_and_escape_sequences() {
+        let keys = parse_keys(b"a\xc3\xa9\x1b[A\x1b[6~\x1b[C\x7f\r\x15\x1b");
+        assert_eq!(
+            vec![
+                Key::Char('a'),
+                Key::Char('é'),
+                Key::Up,
+                Key::PageDown,
+                Key::Backspace,
+                Key::Enter,
+                Key::Ctrl('u'),
+                Key::Escape,
+            ],
+            keys
+        );
+    }
+
+    #[test]
+    fn fits_and_highlights_text() {
+        assert_eq!("ab cd", fit("ab\tcdef", &[], 5));
+        assert_eq!(
+            format!("a{}bc{}d{}ef{}", HIGHLIGHT, RESET, HIGHLIGHT, RESET),
+            fit("abcdef", &[1..3, 4..6], 6)
+        );
+        // A highlight cut off at the edge is still switched off.
+        assert_eq!(
+            format!("a{}bc{}", HIGHLIGHT, RESET),
+            fit("abcdef", &[1..5, 5..6], 3)
+        );
+    }
+
+    #[test]
+    fn keeps_the_selection_in_view() {
+        let mut app = App {
+            files: Vec::new(),
+            query: String::new(),
+            hits: vec![
+                Hit {
+                    file: 0,
+                    line_number: 1,
+                    line: 0..0,
+                    ranges: Vec::new(),
+                };
+                10
+            ],
+            status: String::new(),
+            unreadable: 0,
+            selected: 0,
+            scroll: 0,
+        };
+        app.select_by(6);
+        app.scroll_to_selected(4);
+        assert_eq!((6, 3), (app.selected, app.scroll));
+        app.select_by(100);
+        app.scroll_to_selected(4);
+        assert_eq!((9, 6), (app.selected, app.scroll));
+        app.select_by(-8);
+        app.scroll_to_selected(4);
+        assert_eq!((1, 1), (app.selected, app.scroll));
+
+        assert_eq!(
+            Layout {
+                height: 6,
+                list: 4,
+                preview: 0
+            },
+            Layout::new(6)
+        );
+        assert_eq!(
+            Layout {
+                height: 24,
+                list: 11,
+                preview: 11
+            },
+            Layout::new(24)
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Paths into JSON documents, for `--json-path`.
+//!
+//! ```text
+//! $.request.user
+//! $.items[*].name
+//! $..id
+//! $['odd key'][-1]
+//! ```
+//!
+//! `$` is the whole document. `.key` and `['key']` pick a member of an
+//! object, `[N]` an element of an array (negative counts back from the end),
+//! and `*` picks every member or element. `..` in front of any of them looks
+//! for it at any depth.
+use std::fmt;
+
+use crate::json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(i64),
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    selector: Selector,
+    /// Whether this is a `..` step, which applies at any depth.
+    descendants: bool,
+}
+
+/// A parsed JSON path.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::json;
+/// use minigrep::jsonpath::JsonPath;
+///
+/// let record = json::parse(r#"{"request": {"user": "ann", "tags": ["a", "b"]}}"#).unwrap();
+/// let path = JsonPath::parse("$.request.tags[*]").unwrap();
+/// let found: Vec<_> = path.select(&record).into_iter().map(|(path, _)| path).collect();
+/// assert_eq!(vec!["$.request.tags[0]", "$.request.tags[1]"], found);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+/// A JSON path that couldn't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Byte offset in the path of the step that's wrong.
+    pub at: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad --json-path at byte {}: {}", self.at, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, ParseError> {
+        let mut rest = path.strip_prefix('$').ok_or(ParseError {
+            at: 0,
+            reason: "a path starts with $",
+        })?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            let at = path.len() - rest.len();
+            let error = |reason| ParseError { at, reason };
+            let (descendants, after) = match rest.strip_prefix("..") {
+                Some(after) => (true, after),
+                None => (false, rest),
+            };
+            let (selector, after) = if let Some(after) = after.strip_prefix('[') {
+                bracket(after).map_err(error)?
+            } else if let Some(after) = after.strip_prefix('.') {
+                name(after).map_err(error)?
+            } else if descendants {
+                name(after).map_err(error)?
+            } else {
+                return Err(error("expected . or ["));
+            };
+            steps.push(Step {
+                selector,
+                descendants,
+            });
+            rest = after;
+        }
+        Ok(JsonPath { steps })
+    }
+
+    /// Every value the path picks out of `root`, with the full path to each,
+    /// like `$.items[2].name`.
+    pub fn select<'v>(&self, root: &'v Value) -> Vec<(String, &'v Value)> {
+        let mut nodes = vec![("$".to_string(), root)];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for (path, value) in nodes {
+                if step.descendants {
+                    for (path, value) in all(path, value) {
+                        next.extend(children(&path, value, &step.selector));
+                    }
+                } else {
+                    next.extend(children(&path, value, &step.selector));
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+}
+
+/// Every value in `value` that isn't an array or an object, including
+/// `value` itself, with the full path to each.
+pub fn leaves(path: String, value: &Value) -> Vec<(String, &Value)> {
+    let mut leaves = all(path, value);
+    leaves.retain(|(_, v)| !matches!(v, Value::Array(_) | Value::Object(_)));
+    leaves
+}
+
+/// `value` and everything inside it, parents before children.
+fn all(path: String, value: &Value) -> Vec<(String, &Value)> {
+    let mut found = Vec::new();
+    let mut pending = vec![(path, value)];
+    while let Some((path, value)) = pending.pop() {
+        let mut inside = children(&path, value, &Selector::Any);
+        inside.reverse();
+        pending.extend(inside);
+        found.push((path, value));
+    }
+    found
+}
+
+/// The members or elements of `value` that `selector` picks.
+fn children<'v>(path: &str, value: &'v Value, selector: &Selector) -> Vec<(String, &'v Value)> {
+    let member = |(key, value): &'v (String, Value)| (format!("{}{}", path, segment(key)), value);
+    let element = |(i, value): (usize, &'v Value)| (format!("{}[{}]", path, i), value);
+    match (value, selector) {
+        (Value::Object(members), Selector::Key(key)) => members
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(member)
+            .collect(),
+        (Value::Object(members), Selector::Any) => members.iter().map(member).collect(),
+        (Value::Array(items), Selector::Index(i)) => {
+            let i = if *i < 0 { items.len() as i64 + i } else { *i };
+            let found = usize::try_from(i)
+                .ok()
+                .and_then(|i| Some((i, items.get(i)?)));
+            found.map(element).into_iter().collect()
+        }
+        (Value::Array(items), Selector::Any) => items.iter().enumerate().map(element).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// How an object key is written in a full path: `.key` when it can be,
+/// otherwise `['key']`.
+fn segment(key: &str) -> String {
+    let mut chars = key.chars();
+    let plain = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if plain {
+        format!(".{}", key)
+    } else {
+        format!("['{}']", key.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+/// Parses a `.name` or `.*` step, after the dot.
+fn name(s: &str) -> Result<(Selector, &str), &'static str> {
+    if let Some(rest) = s.strip_prefix('*') {
+        return Ok((Selector::Any, rest));
+    }
+    let end = s.find(['.', '[']).unwrap_or(s.len());
+    if end == 0 {
+        return Err("expected a key name");
+    }
+    Ok((Selector::Key(s[..end].to_string()), &s[end..]))
+}
+
+/// Parses a `[...]` step, after the `[`.
+fn bracket(s: &str) -> Result<(Selector, &str), &'static str> {
+    let (selector, rest) = match s.chars().next() {
+        Some('*') => (Selector::Any, &s[1..]),
+        Some(quote @ ('\'' | '"')) => {
+            let mut key = String::new();
+            let mut chars = s[1..].char_indices();
+            loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => key.push(c),
+                        None => return Err("unclosed quote"),
+                    },
+                    Some((i, c)) if c == quote => break (Selector::Key(key), &s[i + 2..]),
+                    Some((_, c)) => key.push(c),
+                    None => return Err("unclosed quote"),
+                }
+            }
+        }
+        _ => {
+            let end = s.find(']').ok_or("missing ]")?;
+            let index = s[..end]
+                .trim()
+                .parse()
+                .map_err(|_| "expected a key, an index or *")?;
+            (Selector::Index(index), &s[end..])
+        }
+    };
+    let rest = rest.strip_prefix(']').ok_or("missing ]")?;
+    Ok((selector, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    fn select(path: &str, document: &str) -> Vec<String> {
+        let document = json::parse(document).unwrap();
+        let path = JsonPath::parse(path).unwrap();
+        let found = path.select(&document);
+        found
+            .into_iter()
+            .map(|(path, value)| format!("{}={}", path, value))
+            .collect()
+    }
+
+    #[test]
+    fn selects_keys_indexes_and_wildcards() {
+        let doc = r#"{"a": {"b": [10, 20, 30]}, "odd key": {"it's": true}}"#;
+        assert_eq!(vec!["$.a.b[1]=20"], select("$.a.b[1]", doc));
+        assert_eq!(vec!["$.a.b[2]=30"], select("$['a'][\"b\"][-1]", doc));
+        assert_eq!(
+            vec!["$.a.b[0]=10", "$.a.b[1]=20", "$.a.b[2]=30"],
+            select("$.a.b.*", doc)
+        );
+        assert_eq!(
+            vec![r"$['odd key']['it\'s']=true"],
+            select(r"$['odd key']['it\'s']", doc)
+        );
+        assert!(select("$.a.b[3]", doc).is_empty());
+        assert!(select("$.missing.b", doc).is_empty());
+    }
+
+    #[test]
+    fn descendants_are_found_at_any_depth() {
+        let doc = r#"{"id": 1, "items": [{"id": 2}, {"child": {"id": 3}}]}"#;
+        assert_eq!(
+            vec!["$.id=1", "$.items[0].id=2", "$.items[1].child.id=3"],
+            select("$..id", doc)
+        );
+
+        let doc = json::parse(r#"{"user": {"name": "ann", "roles": ["admin"]}}"#).unwrap();
+        let leaves: Vec<_> = leaves("$".to_string(), &doc)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(vec!["$.user.name", "$.user.roles[0]"], leaves);
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        let error = |path| JsonPath::parse(path).unwrap_err();
+        assert_eq!(
+            ParseError {
+                at: 0,
+                reason: "a path starts with $"
+            },
+            error("request.user")
+        );
+        assert_eq!(
+            ParseError {
+                at: 3,
+                reason: "missing ]"
+            },
+            error("$.a[0")
+        );
+        assert_eq!(
+            ParseError {
+                at: 3,
+                reason: "unclosed quote"
+            },
+            error("$.a['b]")
+        );
+        assert_eq!(
+            ParseError {
+                at: 1,
+                reason: "expected a key name"
+            },
+            error("$..")
+        );
+    }
+}
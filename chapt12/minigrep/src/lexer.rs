@@ -0,0 +1,333 @@
+//! Telling code from comments and strings, for `--only`.
+//!
+//! The lexer only knows as much as it needs to find where comments and
+//! string literals start and end. Each [`Language`] describes its syntax
+//! with a few settings, so another C-like language is one more entry in
+//! [`LANGUAGES`].
+use std::ops::Range;
+use std::path::Path;
+
+/// What a stretch of source code is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Code,
+    Comment,
+    /// A string or character literal, quotes and all.
+    String,
+}
+
+impl Region {
+    /// The region `--only` asks for: `code`, `comments` or `strings`.
+    pub fn from_name(name: &str) -> Option<Region> {
+        match name {
+            "code" => Some(Region::Code),
+            "comments" => Some(Region::Comment),
+            "strings" => Some(Region::String),
+            _ => None,
+        }
+    }
+}
+
+/// The syntax of a C-like language: `//` and `/* */` comments, and strings
+/// in quotes with backslash escapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    /// File extensions, without the dot.
+    pub extensions: &'static [&'static str],
+    /// Quotes that start a literal. A `'` literal ends at the end of the
+    /// line if it isn't closed before then.
+    pub quotes: &'static [u8],
+    /// What can go right in front of a quote, like `b` in Rust's `b"bytes"`.
+    pub string_prefixes: &'static [&'static str],
+    /// Prefixes that start a raw string, which has no escapes and can have
+    /// `#`s around its quotes, like Rust's `r#"..."#`.
+    pub raw_prefixes: &'static [&'static str],
+    /// Whether block comments nest, so `/* a /* b */ c */` is all comment.
+    pub nested_comments: bool,
+    /// Whether `'` can start a lifetime or label like `'a`, so only `'x'`
+    /// and `'\n'` are character literals.
+    pub lifetimes: bool,
+}
+
+pub const RUST: Language = Language {
+    name: "rust",
+    extensions: &["rs"],
+    quotes: b"\"'",
+    string_prefixes: &["b", "c"],
+    raw_prefixes: &["r", "br", "cr"],
+    nested_comments: true,
+    lifetimes: true,
+};
+
+pub const C: Language = Language {
+    name: "c",
+    extensions: &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "java", "cs"],
+    quotes: b"\"'",
+    string_prefixes: &["L", "u", "U", "u8"],
+    raw_prefixes: &[],
+    nested_comments: false,
+    lifetimes: false,
+};
+
+pub const JAVASCRIPT: Language = Language {
+    name: "javascript",
+    extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx"],
+    quotes: b"\"'`",
+    string_prefixes: &[],
+    raw_prefixes: &[],
+    nested_comments: false,
+    lifetimes: false,
+};
+
+/// Every language the lexer knows.
+pub const LANGUAGES: &[Language] = &[RUST, C, JAVASCRIPT];
+
+impl Language {
+    /// The language of a file, going by its extension.
+    pub fn for_path(path: &Path) -> Option<&'static Language> {
+        let extension = path.extension()?.to_str()?;
+        LANGUAGES
+            .iter()
+            .find(|language| language.extensions.contains(&extension))
+    }
+}
+
+/// The regions of a source file, in order and with no gaps between them.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::lexer::{self, Region, Regions};
+///
+/// let source = r#"let s = "/* no */"; // x.unwrap()"#;
+/// let regions = Regions::new(source, &lexer::RUST);
+/// let at = source.find("no").unwrap();
+/// assert_eq!(Some(Region::String), regions.of(at..at + 2));
+/// let at = source.find("unwrap").unwrap();
+/// assert_eq!(Some(Region::Comment), regions.of(at..at + 6));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regions(Vec<(Range<usize>, Region)>);
+
+impl Regions {
+    pub fn new(text: &str, language: &Language) -> Regions {
+        let bytes = text.as_bytes();
+        let mut regions = Regions(Vec::new());
+        let mut i = 0;
+        while i < bytes.len() {
+            let (end, region) = token(bytes, i, language);
+            regions.push(i..end, region);
+            i = end;
+        }
+        regions
+    }
+
+    /// Adds a region, merging it into the one before if they're the same.
+    fn push(&mut self, range: Range<usize>, region: Region) {
+        match self.0.last_mut() {
+            Some((last, kind)) if *kind == region && last.end == range.start => {
+                last.end = range.end
+            }
+            _ => self.0.push((range, region)),
+        }
+    }
+
+    /// The region `range` is in, if it's all inside one.
+    pub fn of(&self, range: Range<usize>) -> Option<Region> {
+        let i = self.0.partition_point(|(r, _)| r.end <= range.start);
+        let (r, region) = self.0.get(i)?;
+        (r.start <= range.start && range.end <= r.end).then_some(*region)
+    }
+}
+
+/// Where the token starting at `i` ends, and what region it's in.
+fn token(bytes: &[u8], i: usize, language: &Language) -> (usize, Region) {
+    let next = bytes.get(i + 1).copied();
+    match bytes[i] {
+        b'/' if next == Some(b'/') => (line_end(bytes, i), Region::Comment),
+        b'/' if next == Some(b'*') => (
+            block_comment(bytes, i, language.nested_comments),
+            Region::Comment,
+        ),
+        b'\'' if language.lifetimes => match char_literal(bytes, i) {
+            Some(end) => (end, Region::String),
+            // A lifetime or a label. Its name is lexed as code after this.
+            None => (i + 1, Region::Code),
+        },
+        quote if language.quotes.contains(&quote) => (quoted(bytes, i), Region::String),
+        b if b.is_ascii_alphabetic() || b == b'_' => {
+            let end = bytes[i..]
+                .iter()
+                .position(|b| !b.is_ascii_alphanumeric() && *b != b'_')
+                .map_or(bytes.len(), |n| i + n);
+            match prefixed(bytes, i, end, language) {
+                Some(literal_end) => (literal_end, Region::String),
+                None => (end, Region::Code),
+            }
+        }
+        _ => (i + 1, Region::Code),
+    }
+}
+
+/// If the identifier at `start..end` is a prefix with a literal right after
+/// it, where that literal ends.
+fn prefixed(bytes: &[u8], start: usize, end: usize, language: &Language) -> Option<usize> {
+    let word = std::str::from_utf8(&bytes[start..end]).ok()?;
+    if language.raw_prefixes.contains(&word) {
+        if let Some(literal_end) = raw_string(bytes, end) {
+            return Some(literal_end);
+        }
+    }
+    if !language.string_prefixes.contains(&word) {
+        return None;
+    }
+    match bytes.get(end) {
+        Some(b'\'') if language.lifetimes => char_literal(bytes, end),
+        Some(quote) if language.quotes.contains(quote) => Some(quoted(bytes, end)),
+        _ => None,
+    }
+}
+
+fn line_end(bytes: &[u8], i: usize) -> usize {
+    bytes[i..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |n| i + n)
+}
+
+/// Where the `/* */` comment starting at `i` ends.
+fn block_comment(bytes: &[u8], i: usize, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut j = i;
+    while j < bytes.len() {
+        match &bytes[j..] {
+            [b'/', b'*', ..] if depth == 0 || nested => {
+                depth += 1;
+                j += 2;
+            }
+            [b'*', b'/', ..] => {
+                depth -= 1;
+                j += 2;
+                if depth == 0 {
+                    return j;
+                }
+            }
+            _ => j += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Where the literal starting with the quote at `i` ends, skipping escaped
+/// characters.
+fn quoted(bytes: &[u8], i: usize) -> usize {
+    let quote = bytes[i];
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b if b == quote => return j + 1,
+            b'\n' if quote == b'\'' => return j,
+            _ => j += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Where the character literal starting at `i` ends, or `None` if the `'`
+/// starts a lifetime instead.
+fn char_literal(bytes: &[u8], i: usize) -> Option<usize> {
+    match bytes.get(i + 1)? {
+        b'\\' => Some(quoted(bytes, i)),
+        &first => {
+            // A character is 1 to 4 bytes, and a literal closes right after it.
+            let len = match first {
+                0xf0.. => 4,
+                0xe0.. => 3,
+                0xc0.. => 2,
+                _ => 1,
+            };
+            (bytes.get(i + 1 + len) == Some(&b'\'')).then_some(i + len + 2)
+        }
+    }
+}
+
+/// Where the raw string whose `#`s or opening quote are at `i` ends, or
+/// `None` if there isn't one there.
+fn raw_string(bytes: &[u8], i: usize) -> Option<usize> {
+    let hashes = bytes[i..].iter().take_while(|b| **b == b'#').count();
+    if bytes.get(i + hashes) != Some(&b'"') {
+        return None;
+    }
+    let body = i + hashes + 1;
+    let closed = (body..bytes.len()).find(|&j| {
+        bytes[j] == b'"'
+            && bytes.len() - (j + 1) >= hashes
+            && bytes[j + 1..j + 1 + hashes].iter().all(|b| *b == b'#')
+    });
+    Some(closed.map_or(bytes.len(), |j| j + 1 + hashes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of every region of one kind.
+    fn found<'t>(text: &'t str, language: &Language, kind: Region) -> Vec<&'t str> {
+        let regions = Regions::new(text, language);
+        regions
+            .0
+            .iter()
+            .filter(|(_, region)| *region == kind)
+            .map(|(range, _)| &text[range.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn rust_comments_nest_and_raw_strings_have_no_escapes() {
+        let source = r####"/* a /* b */ still */ let x = r#"say "hi" \"#; // end
+let y = br"\"; let z = r#raw;
+"####;
+        assert_eq!(
+            vec!["/* a /* b */ still */", "// end"],
+            found(source, &RUST, Region::Comment)
+        );
+        assert_eq!(
+            vec![r##"r#"say "hi" \"#"##, r#"br"\""#],
+            found(source, &RUST, Region::String)
+        );
+    }
+
+    #[test]
+    fn rust_chars_are_told_apart_from_lifetimes() {
+        let source =
+            "fn f<'a>(s: &'a str) -> char { 'x'; b'\\''; '\\u{1F980}'; 'é' } 'outer: loop {}";
+        assert_eq!(
+            vec!["'x'", "b'\\''", "'\\u{1F980}'", "'é'"],
+            found(source, &RUST, Region::String)
+        );
+        let code = found(source, &RUST, Region::Code).concat();
+        assert!(code.contains("<'a>(s: &'a str)") && code.contains("'outer: loop"));
+    }
+
+    #[test]
+    fn other_languages_by_extension() {
+        assert_eq!(Some(&C), Language::for_path(Path::new("src/main.cpp")));
+        assert_eq!(None, Language::for_path(Path::new("README.md")));
+
+        let source = "/* a /* b */ c = u8\"x\\\"y\"; char q = '\\''; /* it's */";
+        assert_eq!(
+            vec!["/* a /* b */", "/* it's */"],
+            found(source, &C, Region::Comment)
+        );
+        assert_eq!(
+            vec!["u8\"x\\\"y\"", "'\\''"],
+            found(source, &C, Region::String)
+        );
+        assert_eq!(
+            vec!["`${a} // b`"],
+            found("x = `${a} // b`;", &JAVASCRIPT, Region::String)
+        );
+    }
+}
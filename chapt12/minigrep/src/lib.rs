@@ -4,31 +4,650 @@
 use std::env;
 use std::error::Error;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::thread;
+use std::time::Instant;
+
+pub mod csv;
+pub mod diff;
+pub mod ffi;
+pub mod follow;
+pub mod git;
+pub mod glob;
+pub mod index;
+pub mod input;
+#[cfg(unix)]
+pub mod interactive;
+pub mod json;
+pub mod jsonpath;
+pub mod lexer;
+pub mod matcher;
+pub mod near;
+pub mod normalize;
+pub mod preprocess;
+pub mod printer;
+pub mod query;
+pub mod searcher;
+pub mod server;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+pub mod timestamp;
+pub mod types;
+pub mod walk;
+pub mod watch;
+
+pub use follow::Follower;
+pub use input::MmapChoice;
+pub use jsonpath::JsonPath;
+pub use matcher::{CaseInsensitiveMatcher, LiteralMatcher, Matcher, RegexMatcher};
+pub use near::NearMatcher;
+pub use normalize::NormalizedMatcher;
+pub use preprocess::Preprocessor;
+pub use printer::{ColorChoice, Format, Printer};
+pub use query::QueryMatcher;
+pub use searcher::{Match, Matches, Searcher, SearcherBuilder, Sink};
+pub use stats::Stats;
+pub use types::Types;
+pub use walk::Walk;
+
+use lexer::{Language, Region, Regions};
+use stats::SkipReason;
+use timestamp::{Bound, Window};
+use watch::{Hit, Snapshot};
+
+/// How many words apart `--near` terms can be when `--within` isn't given.
+pub const NEAR_WITHIN: usize = 5;
 
 pub struct Config {
     pub query: String,
-    pub filename: String,
+    pub paths: Vec<String>,
     pub case_sensitive: bool,
+    pub multiline: bool,
+    pub mmap: MmapChoice,
+    pub follow: bool,
+    pub watch: bool,
+    pub index: bool,
+    pub stats: bool,
+    pub format: Format,
+    pub regex: bool,
+    pub only_matching: bool,
+    pub line_number: bool,
+    pub column: bool,
+    pub capture: Option<usize>,
+    pub pre: Option<Preprocessor>,
+    pub follow_links: bool,
+    pub max_depth: Option<usize>,
+    pub max_filesize: Option<u64>,
+    pub one_file_system: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    pub null: bool,
+    pub types: Types,
+    pub type_list: bool,
+    /// The query is a `--query` expression rather than a plain string.
+    pub boolean: bool,
+    /// The two words for `--near`. The query just describes them.
+    pub near: Option<(String, String)>,
+    pub within: usize,
+    pub color: ColorChoice,
+    pub normalize: bool,
+    pub ignore_diacritics: bool,
+    /// A git revision to search instead of the files on disk.
+    pub rev: Option<String>,
+    /// Only match values at this path in JSON and JSON Lines files.
+    pub json_path: Option<JsonPath>,
+    /// Treat files as CSV with this delimiter, for `--csv` and `--tsv`.
+    pub delimiter: Option<char>,
+    /// The columns to match in, by name or 1-based number. All of them if
+    /// there aren't any.
+    pub fields: Vec<String>,
+    /// Run the full-screen UI instead of printing matches.
+    pub interactive: bool,
+    /// Only search log lines stamped inside this window, for `--since` and
+    /// `--until`.
+    pub window: Option<Window>,
+    /// Treat the input as unified diffs and only search the lines they add.
+    pub diff: bool,
+    /// With `diff`, search the lines they remove instead.
+    pub removed: bool,
+    /// Only keep matches in this part of source files, for `--only`.
+    pub only: Option<Region>,
 }
 
 impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
+    pub fn new(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
         args.next();
+
+        let mut query = None;
+        let mut paths = Vec::new();
+        let mut case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        let mut multiline = false;
+        let mut mmap = MmapChoice::Auto;
+        let mut follow = false;
+        let mut watch = false;
+        let mut index = false;
+        let mut stats = false;
+        let mut format = Format::Standard;
+        let mut regex = false;
+        let mut only_matching = false;
+        let mut line_number = false;
+        let mut column = false;
+        let mut capture = None;
+        let mut pre = None;
+        let mut pre_globs = Vec::new();
+        let mut follow_links = false;
+        let mut max_depth = None;
+        let mut max_filesize = None;
+        let mut one_file_system = false;
+        let mut files_with_matches = false;
+        let mut files_without_match = false;
+        let mut null = false;
+        let mut files_from = Vec::new();
+        let mut type_adds = Vec::new();
+        let mut type_selections = Vec::new();
+        let mut type_list = false;
+        let mut boolean = false;
+        let mut near = None;
+        let mut within = None;
+        let mut color = ColorChoice::Auto;
+        let mut normalize = false;
+        let mut ignore_diacritics = false;
+        let mut rev = None;
+        let mut json_path = None;
+        let mut delimiters = Vec::new();
+        let mut fields = Vec::new();
+        let mut interactive = false;
+        let mut window = Window::default();
+        let mut diff = false;
+        let mut removed = false;
+        let mut only = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--insensitive" => case_sensitive = false,
+                "-U" | "--multiline" => multiline = true,
+                "--mmap" => mmap = MmapChoice::Always,
+                "--no-mmap" => mmap = MmapChoice::Never,
+                "--follow" => follow = true,
+                "--watch" => watch = true,
+                "--index" => index = true,
+                "--stats" => stats = true,
+                "--json" => format = Format::Json,
+                "--format" => {
+                    let name = args.next().ok_or("--format needs a format name")?;
+                    format = Format::from_name(&name)
+                        .ok_or("--format must be standard, json, sarif, vimgrep or emacs")?;
+                }
+                "-E" | "--regex" => regex = true,
+                "-o" | "--only-matching" => only_matching = true,
+                "-n" | "--line-number" => line_number = true,
+                "--column" => column = true,
+                "--pre" => {
+                    let command = args.next().ok_or("--pre needs a command")?;
+                    pre = Some(Preprocessor::new(&command).ok_or("--pre needs a command")?);
+                }
+                "--pre-glob" => pre_globs.push(args.next().ok_or("--pre-glob needs a glob")?),
+                "-l" | "--files-with-matches" => files_with_matches = true,
+                "-L" | "--files-without-match" => files_without_match = true,
+                "-0" | "--null" => null = true,
+                "--files-from" => {
+                    let list = args.next().ok_or("--files-from needs a file")?;
+                    let listed = if list == "-" {
+                        walk::read_paths(io::stdin().lock())
+                    } else {
+                        fs::File::open(&list).and_then(walk::read_paths)
+                    };
+                    files_from.extend(listed.map_err(|_| "Couldn't read the --files-from list")?);
+                }
+                "-t" | "--type" => {
+                    let name = args.next().ok_or("-t needs a file type")?;
+                    type_selections.push((name, true));
+                }
+                "-T" | "--type-not" => {
+                    let name = args.next().ok_or("-T needs a file type")?;
+                    type_selections.push((name, false));
+                }
+                "--type-add" => type_adds.push(args.next().ok_or("--type-add needs NAME:GLOB")?),
+                "--type-list" => type_list = true,
+                "--query" => {
+                    query = Some(args.next().ok_or("--query needs an expression")?);
+                    boolean = true;
+                }
+                "--near" => {
+                    let a = args.next().ok_or("--near needs two words")?;
+                    let b = args.next().ok_or("--near needs two words")?;
+                    near = Some((a, b));
+                }
+                "--within" => {
+                    let words = args.next().ok_or("--within needs a number of words")?;
+                    within = Some(
+                        words
+                            .parse()
+                            .map_err(|_| "--within needs a number of words")?,
+                    );
+                }
+                "--color" => {
+                    let choice = args.next().ok_or("--color needs never, always or auto")?;
+                    color = ColorChoice::from_name(&choice)
+                        .ok_or("--color needs never, always or auto")?;
+                }
+                "--normalize" => normalize = true,
+                "--ignore-diacritics" => ignore_diacritics = true,
+                "--json-path" => {
+                    let path = args
+                        .next()
+                        .ok_or("--json-path needs a path like $.request.user")?;
+                    json_path = Some(
+                        JsonPath::parse(&path)
+                            .map_err(|_| "--json-path needs a path like $.request.user")?,
+                    );
+                }
+                "--interactive" => interactive = true,
+                "--diff" => diff = true,
+                "--removed" => removed = true,
+                "--only" => {
+                    let region = args
+                        .next()
+                        .ok_or("--only needs code, comments or strings")?;
+                    only = Some(
+                        Region::from_name(&region)
+                            .ok_or("--only needs code, comments or strings")?,
+                    );
+                }
+                "--since" => {
+                    let since = args
+                        .next()
+                        .ok_or("--since needs a time like 14:00 or 2024-05-01")?;
+                    window.since = Some(
+                        Bound::parse(&since)
+                            .ok_or("--since needs a time like 14:00 or 2024-05-01")?,
+                    );
+                }
+                "--until" => {
+                    let until = args
+                        .next()
+                        .ok_or("--until needs a time like 14:15 or 2024-05-02")?;
+                    window.until = Some(
+                        Bound::parse(&until)
+                            .ok_or("--until needs a time like 14:15 or 2024-05-02")?,
+                    );
+                }
+                "--csv" => delimiters.push(','),
+                "--tsv" => delimiters.push('\t'),
+                "--field" => {
+                    fields.push(args.next().ok_or("--field needs a column name or number")?)
+                }
+                "--rev" => rev = Some(args.next().ok_or("--rev needs a revision")?),
+                "--follow-links" => follow_links = true,
+                "--one-file-system" => one_file_system = true,
+                "--max-depth" => {
+                    let depth = args.next().ok_or("--max-depth needs a number")?;
+                    max_depth = Some(depth.parse().map_err(|_| "--max-depth needs a number")?);
+                }
+                "--max-filesize" => {
+                    let size = args.next().ok_or("--max-filesize needs a size like 10M")?;
+                    max_filesize = Some(
+                        walk::parse_size(&size).ok_or("--max-filesize needs a size like 10M")?,
+                    );
+                }
+                "--capture" => {
+                    let group = args.next().ok_or("--capture needs a group number")?;
+                    capture = Some(
+                        group
+                            .parse()
+                            .map_err(|_| "--capture needs a group number")?,
+                    );
+                }
+                _ => paths.push(arg),
+            }
+        }
+        // Without --query, the first argument that isn't a flag is the query.
+        if !boolean && near.is_none() && !paths.is_empty() {
+            query = Some(paths.remove(0));
+        }
+        if within.is_some() && near.is_none() {
+            return Err("--within only works with --near");
+        }
+        let within = within.unwrap_or(NEAR_WITHIN);
+        if let Some((a, b)) = &near {
+            if !NearMatcher::is_word(a) || !NearMatcher::is_word(b) {
+                return Err("--near takes two single words");
+            }
+            if boolean || regex || multiline {
+                return Err("--near can't be used with --query, --regex or --multiline");
+            }
+            query = Some(format!("{} NEAR/{} {}", a, within, b));
+        }
+        paths.extend(files_from);
+
+        // Types can be added after they're used, so they're only looked up now.
+        let mut types = Types::new();
+        for definition in &type_adds {
+            types
+                .add(definition)
+                .map_err(|_| "--type-add needs NAME:GLOB with a valid glob")?;
+        }
+        for (name, select) in &type_selections {
+            let result = if *select {
+                types.select(name)
+            } else {
+                types.negate(name)
+            };
+            result.map_err(|_| "Unknown file type, see --type-list for the known ones")?;
+        }
+
+        let mut query = match query {
+            Some(query) => query,
+            None if type_list || interactive => String::new(),
+            None => return Err("Didn't get a query string"),
+        };
+        if multiline && !regex {
+            // Typing a real newline into a shell is awkward, so `\n` stands in for one.
+            query = query.replace("\\n", "\n");
+        }
+        if paths.is_empty() && interactive {
+            paths.push(".".to_string());
+        }
+        if paths.is_empty() && !type_list && rev.is_none() && !diff {
+            return Err("Didn't get a filename");
+        }
+        if follow && watch {
+            return Err("Can't use --follow and --watch together");
+        }
+        if boolean && (regex || multiline) {
+            return Err("--query can't be used with --regex or --multiline");
+        }
+        if (normalize || ignore_diacritics) && (regex || near.is_some()) {
+            return Err("--normalize and --ignore-diacritics don't work with --regex or --near");
+        }
+        if capture.is_some() && !regex {
+            return Err("--capture only works with --regex");
+        }
+        match &mut pre {
+            Some(pre) => {
+                for glob in &pre_globs {
+                    pre.glob(glob)
+                        .map_err(|_| "Got an invalid --pre-glob pattern")?;
+                }
+            }
+            None if !pre_globs.is_empty() => return Err("--pre-glob only works with --pre"),
+            None => {}
+        }
+        if pre.is_some() && follow {
+            return Err("Can't use --pre with --follow");
+        }
+        if rev.is_some() && (follow || watch || index || pre.is_some()) {
+            return Err("--rev can't be used with --follow, --watch, --index or --pre");
+        }
+        if json_path.is_some() && (multiline || capture.is_some() || follow || watch) {
+            return Err(
+                "--json-path can't be used with --multiline, --capture, --follow or --watch",
+            );
+        }
+        if json_path.is_some() && !matches!(format, Format::Standard | Format::Json) {
+            return Err("--json-path only works with the standard and json formats");
+        }
+        if delimiters.len() > 1 {
+            return Err("Can't use --csv and --tsv together");
+        }
+        let delimiter = delimiters.pop();
+        if delimiter.is_none() && !fields.is_empty() {
+            return Err("--field only works with --csv or --tsv");
+        }
+        if delimiter.is_some()
+            && (multiline || capture.is_some() || follow || watch || json_path.is_some())
+        {
+            return Err(
+                "--csv and --tsv can't be used with --multiline, --capture, --follow, --watch or --json-path",
+            );
+        }
+        if interactive && cfg!(not(unix)) {
+            return Err("--interactive needs a Unix terminal");
+        }
+        if interactive
+            && (follow
+                || watch
+                || multiline
+                || near.is_some()
+                || rev.is_some()
+                || json_path.is_some()
+                || delimiter.is_some())
+        {
+            return Err(
+                "--interactive can't be used with --follow, --watch, --multiline, --near, --rev, --json-path, --csv or --tsv",
+            );
+        }
+        let window = (window != Window::default()).then_some(window);
+        if window.is_some()
+            && (follow || watch || interactive || json_path.is_some() || delimiter.is_some())
+        {
+            return Err(
+                "--since and --until can't be used with --follow, --watch, --interactive, --json-path, --csv or --tsv",
+            );
+        }
+        if removed && !diff {
+            return Err("--removed only works with --diff");
+        }
+        if diff
+            && (multiline
+                || follow
+                || watch
+                || index
+                || rev.is_some()
+                || interactive
+                || json_path.is_some()
+                || delimiter.is_some()
+                || window.is_some())
+        {
+            return Err(
+                "--diff can't be used with --multiline, --follow, --watch, --index, --rev, --interactive, --json-path, --csv, --tsv, --since or --until",
+            );
+        }
+        if only.is_some()
+            && (follow
+                || watch
+                || interactive
+                || diff
+                || json_path.is_some()
+                || delimiter.is_some()
+                || window.is_some())
+        {
+            return Err(
+                "--only can't be used with --follow, --watch, --interactive, --diff, --json-path, --csv, --tsv, --since or --until",
+            );
+        }
+        if files_with_matches && files_without_match {
+            return Err("Can't use -l and -L together");
+        }
+        if (files_with_matches || files_without_match)
+            && (follow || watch || format != Format::Standard)
+        {
+            return Err("-l and -L don't work with --follow, --watch or --format");
+        }
+        if matches!(format, Format::Json | Format::Sarif) && watch {
+            return Err("Can't use --json or --format sarif with --watch");
+        }
+        if format == Format::Sarif && follow {
+            return Err("Can't use --format sarif with --follow");
+        }
+        if follow && paths.len() > 1 {
+            return Err("--follow only works with a single file");
+        }
+
         Ok(Config {
-            query: match args.next() {
-                Some(arg) => arg,
-                None => return Err("Didn't get a query string"),
-            },
-            filename: match args.next() {
-                Some(arg) => arg,
-                None => return Err("Didn't get a filename"),
-            },
-            case_sensitive: match args.next() {
-                Some(arg) => arg != "--insensitive",
-                None => env::var("CASE_INSENSITIVE").is_err(),
-            },
+            query,
+            paths,
+            case_sensitive,
+            multiline,
+            mmap,
+            follow,
+            watch,
+            index,
+            stats,
+            format,
+            regex,
+            only_matching: only_matching || capture.is_some(),
+            line_number,
+            column,
+            capture,
+            pre,
+            follow_links,
+            max_depth,
+            max_filesize,
+            one_file_system,
+            files_with_matches,
+            files_without_match,
+            null,
+            types,
+            type_list,
+            boolean,
+            near,
+            within,
+            color,
+            normalize,
+            ignore_diacritics,
+            rev,
+            json_path,
+            delimiter,
+            fields,
+            interactive,
+            window,
+            diff,
+            removed,
+            only,
         })
     }
+
+    /// Builds the matcher this config asks for.
+    pub fn matcher(&self) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+        if self.regex {
+            let matcher = RegexMatcher::new(&self.query, self.case_sensitive)?;
+            if let Some(group) = self.capture {
+                if group >= matcher.captures_len() {
+                    return Err(format!(
+                        "--capture {} but the pattern only has {} capture groups",
+                        group,
+                        matcher.captures_len() - 1
+                    )
+                    .into());
+                }
+            }
+            return Ok(Box::new(matcher));
+        }
+        if let Some((a, b)) = &self.near {
+            return Ok(Box::new(NearMatcher::new(
+                a,
+                b,
+                self.within,
+                self.case_sensitive,
+            )));
+        }
+        if self.boolean {
+            let expr = query::parse(&self.query)?;
+            let matcher = QueryMatcher::new(expr, |term| self.term_matcher(&term));
+            return Ok(Box::new(matcher));
+        }
+        Ok(self.term_matcher(&self.query))
+    }
+
+    /// A matcher for a plain string, respecting case sensitivity and
+    /// normalization.
+    fn term_matcher(&self, term: &str) -> Box<dyn Matcher> {
+        if self.normalize || self.ignore_diacritics {
+            Box::new(NormalizedMatcher::new(
+                term,
+                self.ignore_diacritics,
+                self.case_sensitive,
+            ))
+        } else if self.case_sensitive {
+            Box::new(LiteralMatcher::new(term))
+        } else {
+            Box::new(CaseInsensitiveMatcher::new(term))
+        }
+    }
+
+    /// What's being searched for, as the patterns SARIF output makes a rule
+    /// for: each term of a `--query` that counts towards a match, or else the
+    /// query as a whole.
+    pub fn patterns(&self) -> Result<Vec<printer::Pattern>, Box<dyn Error>> {
+        if !self.boolean {
+            return Ok(vec![(self.query.clone(), self.matcher()?)]);
+        }
+        let expr = query::parse(&self.query)?;
+        let mut terms = Vec::new();
+        for term in expr.positive_terms() {
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+        Ok(terms
+            .into_iter()
+            .map(|term| (term.clone(), self.term_matcher(term)))
+            .collect())
+    }
+
+    /// The plain text every match must contain, if there is one. This is what
+    /// the trigram index narrows the search down with, so there isn't one for
+    /// regexes, `--query` expressions, `--near` or normalized matching, or
+    /// when a preprocessor or JSON or CSV quoting change what actually gets
+    /// searched.
+    pub fn literal(&self) -> Option<&str> {
+        let plain = !self.regex && !self.boolean && self.near.is_none();
+        let exact = !self.normalize
+            && !self.ignore_diacritics
+            && self.pre.is_none()
+            && self.json_path.is_none()
+            && self.delimiter.is_none();
+        (plain && exact).then_some(self.query.as_str())
+    }
+
+    /// The literal `--index` narrows the files down with, if it's on and can
+    /// be used. It can't with `-L`, since the files the index rules out are
+    /// the ones `-L` lists.
+    fn index_literal(&self) -> Option<&str> {
+        if !self.index || self.files_without_match {
+            return None;
+        }
+        self.literal()
+    }
+
+    /// Builds a searcher with the options this config asks for.
+    pub fn searcher(&self) -> Searcher {
+        SearcherBuilder::new()
+            .multiline(self.multiline)
+            .capture(self.capture)
+            .build()
+    }
+
+    /// Reads a file ready for searching, running it through the
+    /// preprocessor first if there is one that applies.
+    fn read(&self, path: &Path) -> io::Result<input::Contents> {
+        match &self.pre {
+            Some(pre) if pre.applies_to(path) => pre.run(path).map(input::Contents::Owned),
+            _ => input::read(path, self.mmap),
+        }
+    }
+
+    /// Walks the paths to search with the limits this config asks for.
+    pub fn walk(&self) -> Walk {
+        Walk::new(&self.paths)
+            .follow_links(self.follow_links)
+            .max_depth(self.max_depth)
+            .max_filesize(self.max_filesize)
+            .one_file_system(self.one_file_system)
+            .types(self.types.clone())
+    }
+
+    /// File names are printed once there's more than one file to tell apart.
+    fn with_filename(&self) -> bool {
+        self.rev.is_some()
+            || self.diff
+            || self.paths.len() > 1
+            || self.paths.iter().any(|p| Path::new(p).is_dir())
+    }
 }
 
 /// # Run is the shiz
@@ -36,21 +655,709 @@ impl Config {
 /// Pass in a config struct, and it will do the magic.
 /// Boom! Done. 😀
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
+    if config.type_list {
+        print!("{}", config.types);
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    if config.interactive {
+        return interactive::run(config);
+    }
+
+    let started = Instant::now();
+    let matcher = config.matcher()?;
+    let searcher = config.searcher();
+
+    let stdout = io::stdout();
+    let mut printer = Printer::new(stdout.lock())
+        .multiline(config.multiline)
+        .with_filename(config.with_filename())
+        .line_number(config.line_number || config.json_path.is_some())
+        .column(config.column)
+        .only_matching(config.only_matching)
+        .format(config.format)
+        .color(config.color.enabled())
+        .patterns(config.patterns()?)
+        .null(config.null);
+    printer.stats_mut().files_only = config.files_with_matches || config.files_without_match;
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
+    if config.follow {
+        return follow(&config, &matcher, &searcher, &mut printer);
+    }
+    if config.watch {
+        return watch(&config, &matcher, &searcher, &mut printer);
+    }
+
+    if let Some(rev) = &config.rev {
+        search_rev(&config, rev, &matcher, &searcher, &mut printer)?;
+        printer.finish(started.elapsed(), config.stats)?;
+        return Ok(());
+    }
+
+    if config.diff {
+        search_diffs(&config, &matcher, &searcher, &mut printer)?;
+        printer.finish(started.elapsed(), config.stats)?;
+        return Ok(());
+    }
+
+    let mut files: Vec<_> = config.walk().collect();
+    let too_large =
+        |file: &Result<_, walk::WalkError>| file.as_ref().is_err_and(|e| e.is_too_large());
+    if files.len() == 1 && !too_large(&files[0]) {
+        // A single file behaves like it always has: any error is fatal.
+        let file = files.pop().unwrap()?;
+        let contents = config.read(&file)?;
+        printer.stats_mut().add_searched(contents.len());
+        search_file(
+            &config,
+            &matcher,
+            &searcher,
+            &mut printer,
+            &file,
+            &contents,
+            None,
+        )?;
     } else {
-        search_case_insensitive(&config.query, &contents)
+        if let Some(literal) = config.index_literal() {
+            let mut narrower = index::Narrower::new(&config.paths, literal)?;
+            files.retain(|file| match file {
+                Ok(file) if !narrower.keep(file) => {
+                    printer.stats_mut().add_skipped(SkipReason::Index);
+                    false
+                }
+                _ => true,
+            });
+            if let Err(e) = narrower.save() {
+                eprintln!("minigrep: couldn't update the index: {}", e);
+            }
+        }
+        search_files(&config, &matcher, &searcher, &mut printer, files, None)?;
+    }
+
+    printer.finish(started.elapsed(), config.stats)?;
+    Ok(())
+}
+
+/// Searches each file in turn, printing matches as it goes and, if asked,
+/// keeping hold of them in `hits`.
+///
+/// Files that can't be read are reported on stderr and skipped.
+fn search_files<W: Write>(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+    files: impl IntoIterator<Item = Result<impl AsRef<Path>, walk::WalkError>>,
+    mut hits: Option<&mut Vec<Hit>>,
+) -> io::Result<()> {
+    for file in files {
+        let file = match file {
+            Ok(file) => file,
+            Err(e) if e.is_too_large() => {
+                printer.stats_mut().add_skipped(SkipReason::TooLarge);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("minigrep: {}", e);
+                printer.stats_mut().add_skipped(SkipReason::Unreadable);
+                continue;
+            }
+        };
+        let file = file.as_ref();
+        let contents = match config.read(file) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("minigrep: {}: {}", file.display(), e);
+                printer.stats_mut().add_skipped(match e.kind() {
+                    io::ErrorKind::InvalidData => SkipReason::NotUtf8,
+                    _ => SkipReason::Unreadable,
+                });
+                continue;
+            }
+        };
+        printer.stats_mut().add_searched(contents.len());
+        search_file(
+            config,
+            matcher,
+            searcher,
+            printer,
+            file,
+            &contents,
+            hits.as_deref_mut(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Searches the files of a git revision, read straight out of the repository
+/// the current directory is in. Paths are relative to the top of the
+/// repository, and any paths given narrow it down to what's under them.
+fn search_rev<W: Write>(
+    config: &Config,
+    rev: &str,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+) -> Result<(), Box<dyn Error>> {
+    let repo = git::Repository::discover(env::current_dir()?)?;
+    let pathspecs = config
+        .paths
+        .iter()
+        .map(|path| repo.pathspec(path))
+        .collect::<io::Result<Vec<_>>>()?;
+    let wanted = |path: &str| {
+        pathspecs.is_empty()
+            || pathspecs.iter().any(|spec| {
+                spec.is_empty()
+                    || path == spec
+                    || path
+                        .strip_prefix(spec.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+    };
+
+    let tree = repo.tree(repo.resolve(rev)?)?;
+    for (path, id) in repo.files(tree)? {
+        if !wanted(&path) || !config.types.is_match(&path) {
+            continue;
+        }
+        let blob = repo.read(id)?;
+        if config
+            .max_filesize
+            .is_some_and(|max| blob.data.len() as u64 > max)
+        {
+            printer.stats_mut().add_skipped(SkipReason::TooLarge);
+            continue;
+        }
+        let contents = match String::from_utf8(blob.data) {
+            Ok(contents) => contents,
+            Err(_) => {
+                eprintln!("minigrep: {}: stream did not contain valid UTF-8", path);
+                printer.stats_mut().add_skipped(SkipReason::NotUtf8);
+                continue;
+            }
+        };
+        printer.stats_mut().add_searched(contents.len());
+        search_file(
+            config,
+            matcher,
+            searcher,
+            printer,
+            Path::new(&path),
+            &contents,
+            None,
+        )?;
+    }
+    Ok(())
+}
+
+/// Searches the diffs named in the config's paths, or the one on stdin if
+/// there aren't any.
+fn search_diffs<W: Write>(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+) -> Result<(), Box<dyn Error>> {
+    if config.paths.is_empty() {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        printer.stats_mut().add_searched(contents.len());
+        search_diff(config, matcher, searcher, printer, &contents)?;
+        return Ok(());
+    }
+    for path in &config.paths {
+        let contents = config.read(Path::new(path))?;
+        printer.stats_mut().add_searched(contents.len());
+        search_diff(config, matcher, searcher, printer, &contents)?;
+    }
+    Ok(())
+}
+
+/// Searches the lines a diff adds to each file (or removes, for
+/// `--removed`), reporting hits under the file's path and with its line
+/// numbers rather than the diff's.
+fn search_diff<W: Write>(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+    contents: &str,
+) -> io::Result<()> {
+    let listing = config.files_with_matches || config.files_without_match;
+    for file in diff::parse(contents) {
+        let lines = if config.removed {
+            &file.removed
+        } else {
+            &file.added
+        };
+        let text = lines
+            .iter()
+            .map(|line| line.text)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let path = Path::new(file.path);
+        let mut matched = false;
+        searcher.search(matcher, &text, &mut |m: &Match| -> io::Result<bool> {
+            let line = &lines[m.line_number - 1];
+            let m = Match {
+                line_number: line.line_number,
+                end_line_number: line.line_number,
+                offset: line.offset,
+                ..m.clone()
+            };
+            matched = true;
+            if listing {
+                return Ok(false);
+            }
+            printer.print(path, &m, 0)?;
+            Ok(true)
+        })?;
+        if listing && matched {
+            printer.stats_mut().add_file_matched();
+        }
+        if listing && matched == config.files_with_matches {
+            printer.print_path(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Searches the contents of one file, printing its matches or, with `-l` or
+/// `-L`, just its name.
+fn search_file<W: Write>(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+    file: &Path,
+    contents: &str,
+    mut hits: Option<&mut Vec<Hit>>,
+) -> io::Result<()> {
+    if config.files_with_matches || config.files_without_match {
+        let mut matched = false;
+        search_contents(
+            config,
+            matcher,
+            searcher,
+            file,
+            contents,
+            &mut |_: &Match| {
+                matched = true;
+                Ok(false)
+            },
+        )?;
+        if matched {
+            printer.stats_mut().add_file_matched();
+        }
+        if matched == config.files_with_matches {
+            printer.print_path(file)?;
+        }
+        return Ok(());
+    }
+
+    search_contents(
+        config,
+        matcher,
+        searcher,
+        file,
+        contents,
+        &mut |m: &Match| {
+            printer.print(file, m, 0)?;
+            if let Some(hits) = hits.as_deref_mut() {
+                hits.push(Hit {
+                    path: file.to_path_buf(),
+                    line_number: m.line_number,
+                    text: m.text.to_string(),
+                });
+            }
+            Ok(true)
+        },
+    )
+}
+
+/// Hands every match in `contents` to `sink`, searching line by line, through
+/// the values `--json-path` picks out, or through CSV records.
+fn search_contents(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    file: &Path,
+    contents: &str,
+    sink: &mut impl FnMut(&Match) -> io::Result<bool>,
+) -> io::Result<()> {
+    if let Some(delimiter) = config.delimiter {
+        return search_csv(delimiter, &config.fields, matcher, file, contents, sink);
+    }
+    if let Some(window) = &config.window {
+        return search_window(window, matcher, searcher, contents, sink);
+    }
+    if let Some(only) = config.only {
+        return search_regions(only, matcher, searcher, file, contents, sink);
+    }
+    match &config.json_path {
+        Some(path) => search_json(path, matcher, file, contents, sink),
+        None => searcher.search(matcher, contents, sink),
+    }
+}
+
+/// Keeps only the matches in `only`, the code, comments or strings of a
+/// source file. Files in languages the lexer doesn't know are skipped.
+fn search_regions(
+    only: Region,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    file: &Path,
+    contents: &str,
+    sink: &mut impl FnMut(&Match) -> io::Result<bool>,
+) -> io::Result<()> {
+    let Some(language) = Language::for_path(file) else {
+        return Ok(());
     };
+    let regions = Regions::new(contents, language);
+    searcher.search(matcher, contents, &mut |m: &Match| -> io::Result<bool> {
+        let mut m = m.clone();
+        m.ranges
+            .retain(|r| regions.of(m.offset + r.start..m.offset + r.end) == Some(only));
+        if m.ranges.is_empty() {
+            return Ok(true);
+        }
+        sink(&m)
+    })
+}
+
+/// Searches only the runs of lines stamped inside `window`, numbering hits
+/// as if the whole of `contents` had been searched.
+fn search_window(
+    window: &Window,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    contents: &str,
+    sink: &mut impl FnMut(&Match) -> io::Result<bool>,
+) -> io::Result<()> {
+    let mut more = true;
+    for (first_line, run) in window.runs(contents) {
+        searcher.search(
+            matcher,
+            &contents[run.clone()],
+            &mut |m: &Match| -> io::Result<bool> {
+                let m = Match {
+                    line_number: m.line_number + first_line - 1,
+                    end_line_number: m.end_line_number + first_line - 1,
+                    offset: m.offset + run.start,
+                    ..m.clone()
+                };
+                more = sink(&m)?;
+                Ok(more)
+            },
+        )?;
+        if !more {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Matches against the fields of each record after the header, or just the
+/// ones in the `fields` columns if there are any.
+///
+/// Every hit is the whole record, with ranges pointing at the matches inside
+/// its quoted fields. The header is handed over first as a match with no
+/// ranges, before the file's first hit.
+fn search_csv<'c>(
+    delimiter: char,
+    fields: &[String],
+    matcher: &impl Matcher,
+    file: &Path,
+    contents: &'c str,
+    sink: &mut impl FnMut(&Match) -> io::Result<bool>,
+) -> io::Result<()> {
+    let mut records = csv::records(contents, delimiter);
+    let Some(header) = records.next() else {
+        return Ok(());
+    };
+    let columns = match csv::columns(&header, fields) {
+        Ok(columns) => columns,
+        Err(field) => {
+            eprintln!("minigrep: {}: no column {:?}", file.display(), field);
+            return Ok(());
+        }
+    };
+    let as_match = |record: &csv::Record<'c>, ranges| Match {
+        line_number: record.line_number,
+        end_line_number: record.end_line_number,
+        offset: record.offset,
+        text: record.text,
+        ranges,
+    };
+
+    let mut header_sent = false;
+    for record in records {
+        let mut ranges = Vec::new();
+        for (i, field) in record.fields.iter().enumerate() {
+            if columns.is_empty() || columns.contains(&i) {
+                ranges.extend(matcher.find_iter(&field.value).map(|r| field.raw(r)));
+            }
+        }
+        if ranges.is_empty() {
+            continue;
+        }
+        if !header_sent {
+            header_sent = true;
+            if !sink(&as_match(&header, Vec::new()))? {
+                return Ok(());
+            }
+        }
+        if !sink(&as_match(&record, ranges))? {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Matches against the values `path` picks out of a JSON document, or out of
+/// each record of a JSON Lines file.
+///
+/// Each hit is a match whose line number is the record number (always 1 for
+/// a plain document) and whose text is the full path to the value and the
+/// value itself. Records that aren't valid JSON are reported and skipped.
+fn search_json(
+    path: &JsonPath,
+    matcher: &impl Matcher,
+    file: &Path,
+    contents: &str,
+    sink: &mut impl FnMut(&Match) -> io::Result<bool>,
+) -> io::Result<()> {
+    let mut records = Vec::new();
+    match json::parse(contents) {
+        Ok(document) => records.push((1, 0, document)),
+        Err(whole) => {
+            let mut offset = 0;
+            let mut number = 0;
+            let mut errors = Vec::new();
+            for line in contents.split_inclusive('\n') {
+                let start = offset;
+                offset += line.len();
+                if line.trim().is_empty() {
+                    continue;
+                }
+                number += 1;
+                match json::parse(line) {
+                    Ok(record) => records.push((number, start, record)),
+                    Err(e) => errors.push((number, e)),
+                }
+            }
+            if records.is_empty() {
+                // Not JSON Lines either, so the error about the whole file is
+                // the useful one.
+                eprintln!("minigrep: {}: {}", file.display(), whole);
+                return Ok(());
+            }
+            for (number, e) in errors {
+                eprintln!("minigrep: {}: record {}: {}", file.display(), number, e);
+            }
+        }
+    }
+
+    for (number, offset, record) in &records {
+        for (selected_path, selected) in path.select(record) {
+            for (full_path, value) in jsonpath::leaves(selected_path, selected) {
+                let raw = match value {
+                    json::Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                };
+                let ranges: Vec<_> = matcher.find_iter(&raw).collect();
+                if ranges.is_empty() {
+                    continue;
+                }
+                let (value, ranges) = encoded(value, &raw, ranges);
+                let prefix = format!("{}: ", full_path);
+                let text = format!("{}{}", prefix, value);
+                let shift = |r: Range<usize>| r.start + prefix.len()..r.end + prefix.len();
+                let m = Match {
+                    line_number: *number,
+                    end_line_number: *number,
+                    offset: *offset,
+                    text: &text,
+                    ranges: ranges.into_iter().map(shift).collect(),
+                };
+                if !sink(&m)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a value out as JSON, moving `ranges` of its unescaped text to where
+/// they end up once it's quoted and escaped.
+fn encoded(
+    value: &json::Value,
+    raw: &str,
+    ranges: Vec<Range<usize>>,
+) -> (String, Vec<Range<usize>>) {
+    if !matches!(value, json::Value::String(_)) {
+        return (raw.to_string(), ranges);
+    }
+    let mut out = String::from('"');
+    let mut moved = vec![0; raw.len() + 1];
+    for (i, c) in raw.char_indices() {
+        moved[i] = out.len();
+        let escaped = json::string(c.encode_utf8(&mut [0; 4]));
+        out.push_str(&escaped[1..escaped.len() - 1]);
+    }
+    moved[raw.len()] = out.len();
+    out.push('"');
+    let ranges = ranges
+        .into_iter()
+        .map(|r| moved[r.start]..moved[r.end])
+        .collect();
+    (out, ranges)
+}
 
-    for el in results {
-        println!("{}", el);
+/// Searches what's already in the file, then keeps polling it for new lines
+/// until the process is killed.
+fn follow<W: Write>(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+) -> Result<(), Box<dyn Error>> {
+    let mut follower = Follower::new(&config.paths[0])?;
+    let mut pending = Pending::default();
+
+    loop {
+        let update = follower.poll()?;
+        if update.truncated {
+            eprintln!("minigrep: {}: file truncated", config.paths[0]);
+        }
+        search_update(
+            matcher,
+            searcher,
+            printer,
+            follower.path(),
+            &update,
+            &mut pending,
+        )?;
+        printer.out().flush()?;
+        if update.rotated {
+            eprintln!(
+                "minigrep: {}: file has been replaced; following new file",
+                config.paths[0]
+            );
+        }
+        thread::sleep(follow::POLL_INTERVAL);
+    }
+}
+
+/// The line a followed file is still writing, as it was last searched.
+#[derive(Debug, Default)]
+struct Pending {
+    text: String,
+    /// Whether it matched, so it was printed already.
+    printed: bool,
+}
+
+/// Prints the matches in what [`Follower::poll`] read: the new lines, then
+/// the unfinished line after them if it changed since it was last searched.
+///
+/// Once that line is finished it's searched again, since the rest of it may
+/// match, but it isn't printed twice if nothing was added to it but its
+/// newline.
+fn search_update<W: Write>(
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+    path: &Path,
+    update: &follow::Update,
+    pending: &mut Pending,
+) -> Result<(), Box<dyn Error>> {
+    if update.truncated {
+        *pending = Pending::default();
+    }
+    let skipped = update.first_line.saturating_sub(1);
+    if !update.text.is_empty() {
+        let finished = update.text.lines().next();
+        let shown = pending.printed && finished == Some(pending.text.as_str());
+        searcher.search(matcher, &update.text, &mut |m: &Match| {
+            if shown && m.line_number == 1 {
+                return Ok(true);
+            }
+            printer.print(path, m, skipped).map(|_| true)
+        })?;
+        *pending = Pending::default();
     }
 
+    if update.partial != pending.text {
+        pending.text = update.partial.clone();
+        pending.printed = false;
+        let skipped = skipped + update.text.lines().count();
+        searcher.search(matcher, &update.partial, &mut |m: &Match| {
+            pending.printed = true;
+            printer.print(path, m, skipped).map(|_| true)
+        })?;
+    }
     Ok(())
 }
 
+/// Runs the search, then runs it again every time a file under the searched
+/// paths is created, modified or deleted, summarising which matches came and
+/// went. Runs until the process is killed.
+fn watch<W: Write>(
+    config: &Config,
+    matcher: &impl Matcher,
+    searcher: &Searcher,
+    printer: &mut Printer<W>,
+) -> Result<(), Box<dyn Error>> {
+    let mut snapshot = Snapshot::take(config.walk());
+    let mut hits = Vec::new();
+    let files = config.walk();
+    search_files(config, matcher, searcher, printer, files, Some(&mut hits))?;
+    printer.out().flush()?;
+
+    loop {
+        thread::sleep(watch::POLL_INTERVAL);
+        let newer = Snapshot::take(config.walk());
+        let changes = newer.changes_since(&snapshot);
+        if changes.is_empty() {
+            continue;
+        }
+
+        writeln!(printer.out(), "\n===== {} =====", changes)?;
+        let mut new_hits = Vec::new();
+        let files = newer.files().map(Ok);
+        search_files(
+            config,
+            matcher,
+            searcher,
+            printer,
+            files,
+            Some(&mut new_hits),
+        )?;
+
+        let diff = watch::diff(&hits, &new_hits);
+        let out = printer.out();
+        writeln!(
+            out,
+            "----- {} appeared, {} disappeared -----",
+            diff.appeared.len(),
+            diff.disappeared.len()
+        )?;
+        for hit in diff.appeared {
+            writeln!(out, "+{}", hit)?;
+        }
+        for hit in diff.disappeared {
+            writeln!(out, "-{}", hit)?;
+        }
+        out.flush()?;
+
+        snapshot = newer;
+        hits = new_hits;
+    }
+}
+
 /// Returns a list of lines that contain a given query
 ///
 /// # Examples
@@ -68,13 +1375,56 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
 /// assert_eq!(vec!["safe, fast, productive."], search(query, contents));
 /// ```
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents.lines().filter(|x| x.contains(query)).collect()
+    let matcher = LiteralMatcher::new(query);
+    Searcher::new()
+        .find_iter(&matcher, contents)
+        .map(|m| m.text)
+        .collect()
 }
 
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents
-        .lines()
-        .filter(|x| x.to_lowercase().contains(&query.to_lowercase()))
+    let matcher = CaseInsensitiveMatcher::new(query);
+    Searcher::new()
+        .find_iter(&matcher, contents)
+        .map(|m| m.text)
+        .collect()
+}
+
+/// Searches the whole of `contents` at once, so the query may span line breaks.
+///
+/// Each [`Match`] covers every line touched by a match; matches that touch
+/// overlapping lines are merged into a single block.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::search_multiline;
+///
+/// let contents = "\
+/// fn add(
+///     a: i32,
+///     b: i32,
+/// ) -> i32 {";
+///
+/// let matches = search_multiline("add(\n    a", contents, true);
+/// assert_eq!(1, matches[0].line_number);
+/// assert_eq!(2, matches[0].end_line_number);
+/// assert_eq!(vec!["fn add(", "    a: i32,"], matches[0].lines().collect::<Vec<_>>());
+/// ```
+pub fn search_multiline<'a>(
+    query: &str,
+    contents: &'a str,
+    case_sensitive: bool,
+) -> Vec<Match<'a>> {
+    let matcher: Box<dyn Matcher> = if case_sensitive {
+        Box::new(LiteralMatcher::new(query))
+    } else {
+        Box::new(CaseInsensitiveMatcher::new(query))
+    };
+    SearcherBuilder::new()
+        .multiline(true)
+        .build()
+        .find_iter(&matcher, contents)
         .collect()
 }
 
@@ -108,4 +1458,195 @@ Trust me.";
             search_case_insensitive(query, contents),
         );
     }
+
+    #[test]
+    fn multiline_spans_line_breaks() {
+        let query = "productive.\nPick";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        let matches = search_multiline(query, contents, true);
+        assert_eq!(1, matches.len());
+        assert_eq!((2, 3), (matches[0].line_number, matches[0].end_line_number));
+        assert_eq!("safe, fast, productive.\nPick three.", matches[0].text);
+    }
+
+    #[test]
+    fn multiline_merges_overlapping_blocks() {
+        let contents = "a b\nb a\nc";
+
+        let matches = search_multiline("B\nB", contents, false);
+        assert_eq!(1, matches.len());
+        assert_eq!((1, 2), (matches[0].line_number, matches[0].end_line_number));
+
+        let matches = search_multiline("a", contents, true);
+        assert_eq!(2, matches.len());
+        assert_eq!("b a", matches[1].text);
+    }
+
+    #[test]
+    fn followed_lines_finished_after_the_first_search_are_searched_whole() {
+        let path = testing::temp_path("follow-partial.log");
+        let searcher = SearcherBuilder::new().build();
+        // Follows a file holding "one\nfo", appending each of `appended`
+        // before a poll, and returns what was printed.
+        let follow = |query: &str, appended: &[&str]| {
+            fs::write(&path, "one\nfo").unwrap();
+            let mut follower = Follower::new(&path).unwrap();
+            let matcher = LiteralMatcher::new(query);
+            let mut printer = Printer::new(Vec::new()).line_number(true);
+            let mut pending = Pending::default();
+            for more in appended {
+                let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+                file.write_all(more.as_bytes()).unwrap();
+                let update = follower.poll().unwrap();
+                search_update(
+                    &matcher,
+                    &searcher,
+                    &mut printer,
+                    &path,
+                    &update,
+                    &mut pending,
+                )
+                .unwrap();
+            }
+            String::from_utf8(printer.out().clone()).unwrap()
+        };
+
+        assert_eq!("2:four\n", follow("four", &["", "ur\nzero\n"]));
+        assert_eq!(
+            "1:one\n2:fo\n2:four\n3:zero\n",
+            follow("o", &["", "", "ur\nzero\n"])
+        );
+        assert_eq!("2:fo\n", follow("fo", &["", "\n"]));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interactive_starts_with_the_query_given() {
+        let config = |args: &[&str]| {
+            let args = ["minigrep", "--interactive"].iter().chain(args);
+            Config::new(args.map(|a| a.to_string())).unwrap()
+        };
+        let given = config(&["todo", "src"]);
+        assert_eq!("todo", given.query);
+        assert_eq!(vec!["src"], given.paths);
+        let empty = config(&[]);
+        assert_eq!("", empty.query);
+        assert_eq!(vec!["."], empty.paths);
+    }
+
+    #[test]
+    fn files_from_adds_paths_after_the_query() {
+        let list = testing::temp_path("files-from.txt");
+        fs::write(&list, "a.txt\nb c.txt\n").unwrap();
+        let args = [
+            "minigrep",
+            "--files-from",
+            list.to_str().unwrap(),
+            "hello",
+            "d.txt",
+        ];
+        let config = Config::new(args.iter().map(|a| a.to_string())).unwrap();
+        assert_eq!("hello", config.query);
+        assert_eq!(vec!["d.txt", "a.txt", "b c.txt"], config.paths);
+        fs::remove_file(list).unwrap();
+    }
+
+    #[test]
+    fn index_is_not_used_for_files_without_match() {
+        let config = |args: &[&str]| {
+            let args = ["minigrep"].iter().chain(args).map(|a| a.to_string());
+            Config::new(args).unwrap()
+        };
+        assert_eq!(
+            Some("hello"),
+            config(&["--index", "-l", "hello", "."]).index_literal()
+        );
+        assert_eq!(
+            None,
+            config(&["--index", "-L", "hello", "."]).index_literal()
+        );
+        assert_eq!(None, config(&["-l", "hello", "."]).index_literal());
+    }
+
+    #[test]
+    fn json_path_hits_carry_the_record_number_and_full_path() {
+        let contents = "{\"user\": \"ann\"}\nnot json\n\n{\"user\": [\"a\\\"nn\", \"bob\"]}\n";
+        let path = JsonPath::parse("$.user").unwrap();
+        let matcher = LiteralMatcher::new("nn");
+        let mut hits = Vec::new();
+        search_json(
+            &path,
+            &matcher,
+            Path::new("log"),
+            contents,
+            &mut |m: &Match| {
+                let found: Vec<_> = m.ranges.iter().map(|r| &m.text[r.clone()]).collect();
+                hits.push((m.line_number, m.text.to_string(), found.concat()));
+                Ok(true)
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                (1, r#"$.user: "ann""#.to_string(), "nn".to_string()),
+                (3, r#"$.user[0]: "a\"nn""#.to_string(), "nn".to_string()),
+            ],
+            hits
+        );
+    }
+
+    #[test]
+    fn json_lines_after_a_broken_first_record_are_still_searched() {
+        let contents = "{oops\n\n{\"user\": \"alice\"}\n";
+        let path = JsonPath::parse("$.user").unwrap();
+        let matcher = LiteralMatcher::new("alice");
+        let mut hits = Vec::new();
+        search_json(
+            &path,
+            &matcher,
+            Path::new("log"),
+            contents,
+            &mut |m: &Match| {
+                hits.push((m.line_number, m.text.to_string()));
+                Ok(true)
+            },
+        )
+        .unwrap();
+        assert_eq!(vec![(2, r#"$.user: "alice""#.to_string())], hits);
+    }
+
+    #[test]
+    fn window_hits_keep_their_place_in_the_file() {
+        let contents = "\
+May  1 13:59:00 ERROR early
+May  1 14:01:00 ERROR one
+  ERROR in trace
+May  1 14:20:00 ERROR late
+";
+        let window = Window {
+            since: Bound::parse("14:00"),
+            until: Bound::parse("14:15"),
+        };
+        let matcher = LiteralMatcher::new("ERROR");
+        let searcher = SearcherBuilder::new().build();
+        let mut hits = Vec::new();
+        search_window(&window, &matcher, &searcher, contents, &mut |m: &Match| {
+            hits.push((m.line_number, m.offset, m.text.to_string()));
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(
+            vec![
+                (2, 28, "May  1 14:01:00 ERROR one".to_string()),
+                (3, 54, "  ERROR in trace".to_string()),
+            ],
+            hits
+        );
+    }
 }
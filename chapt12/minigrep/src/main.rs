@@ -1,10 +1,28 @@
 use std::env;
+use std::io;
 use std::process;
 
 use minigrep::Config;
 
 fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|err| {
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 2 && args[1] == "--server" {
+        let input = io::BufReader::new(io::stdin());
+        if let Err(e) = minigrep::server::serve(input, io::stdout().lock()) {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(dir) = minigrep::index::build_command(&args) {
+        if let Err(e) = minigrep::index::command(dir) {
+            eprintln!("Application error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let config = Config::new(args.into_iter()).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
         process::exit(1);
     });
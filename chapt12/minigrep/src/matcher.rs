@@ -0,0 +1,278 @@
+//! Matchers decide *where* a query hits inside a piece of text.
+//!
+//! Everything that knows how to find a query implements [`Matcher`], so the
+//! [`Searcher`](crate::Searcher) doesn't care whether it's looking for a plain
+//! string, ignoring case, or using some other engine entirely.
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+/// Something that can find matches inside a haystack.
+pub trait Matcher {
+    /// Returns the byte range of the first match in `haystack` that starts at
+    /// or after byte `at`.
+    ///
+    /// `at` is always on a char boundary. Ranges are relative to the start of
+    /// `haystack`, not to `at`.
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>>;
+
+    /// Returns true if there is a match anywhere in `haystack`.
+    fn is_match(&self, haystack: &str) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    /// Narrows the match at `m` down to capture group `group`, or returns
+    /// `None` if that group didn't take part in the match.
+    ///
+    /// Group 0 is the whole match. Engines without capture groups don't have
+    /// any others.
+    fn capture(&self, haystack: &str, m: Range<usize>, group: usize) -> Option<Range<usize>> {
+        let _ = haystack;
+        (group == 0).then_some(m)
+    }
+
+    /// Returns every non-overlapping match in `haystack`, lazily.
+    fn find_iter<'m, 'h>(&'m self, haystack: &'h str) -> FindIter<'m, 'h, Self>
+    where
+        Self: Sized,
+    {
+        FindIter {
+            matcher: self,
+            haystack,
+            at: Some(0),
+        }
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for &M {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        (**self).find_at(haystack, at)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        (**self).is_match(haystack)
+    }
+
+    fn capture(&self, haystack: &str, m: Range<usize>, group: usize) -> Option<Range<usize>> {
+        (**self).capture(haystack, m, group)
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        (**self).find_at(haystack, at)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        (**self).is_match(haystack)
+    }
+
+    fn capture(&self, haystack: &str, m: Range<usize>, group: usize) -> Option<Range<usize>> {
+        (**self).capture(haystack, m, group)
+    }
+}
+
+/// Iterator over the matches of a [`Matcher`], created by [`Matcher::find_iter`].
+pub struct FindIter<'m, 'h, M> {
+    matcher: &'m M,
+    haystack: &'h str,
+    at: Option<usize>,
+}
+
+impl<M: Matcher> Iterator for FindIter<'_, '_, M> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let at = self.at?;
+        let found = self.matcher.find_at(self.haystack, at);
+        self.at = match &found {
+            Some(m) if m.end > m.start => Some(m.end),
+            // Step past empty matches so we always make progress.
+            Some(m) => self.haystack[m.start..]
+                .chars()
+                .next()
+                .map(|c| m.start + c.len_utf8()),
+            None => None,
+        };
+        found
+    }
+}
+
+/// Matches a query exactly as written.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::{LiteralMatcher, Matcher};
+///
+/// let matcher = LiteralMatcher::new("duct");
+/// assert_eq!(Some(15..19), matcher.find_at("safe, fast, productive.", 0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LiteralMatcher {
+    needle: String,
+}
+
+impl LiteralMatcher {
+    pub fn new(needle: &str) -> LiteralMatcher {
+        LiteralMatcher {
+            needle: needle.to_string(),
+        }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        haystack[at..]
+            .find(&self.needle)
+            .map(|i| at + i..at + i + self.needle.len())
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        haystack.contains(&self.needle)
+    }
+}
+
+/// Matches a query regardless of case.
+///
+/// Returned ranges always point into the original haystack, even when
+/// lowercasing would change how many bytes a character takes up.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::{CaseInsensitiveMatcher, Matcher};
+///
+/// let matcher = CaseInsensitiveMatcher::new("rUsT");
+/// assert!(matcher.is_match("Trust me."));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaseInsensitiveMatcher {
+    needle: Vec<char>,
+}
+
+impl CaseInsensitiveMatcher {
+    pub fn new(needle: &str) -> CaseInsensitiveMatcher {
+        CaseInsensitiveMatcher {
+            needle: needle.chars().flat_map(char::to_lowercase).collect(),
+        }
+    }
+
+    /// Returns the end of a match starting at byte `start`, if there is one.
+    fn match_len_at(&self, haystack: &str, start: usize) -> Option<usize> {
+        let mut matched = 0;
+        for (i, c) in haystack[start..].char_indices() {
+            for lower in c.to_lowercase() {
+                if self.needle.get(matched) != Some(&lower) {
+                    return None;
+                }
+                matched += 1;
+            }
+            if matched == self.needle.len() {
+                return Some(start + i + c.len_utf8());
+            }
+        }
+        None
+    }
+}
+
+impl Matcher for CaseInsensitiveMatcher {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        if self.needle.is_empty() {
+            return Some(at..at);
+        }
+        haystack[at..]
+            .char_indices()
+            .find_map(|(i, _)| self.match_len_at(haystack, at + i).map(|end| at + i..end))
+    }
+}
+
+/// Matches a regular expression, using the `regex` crate's syntax.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::{Matcher, RegexMatcher};
+///
+/// let matcher = RegexMatcher::new(r"req-(\d+)", true).unwrap();
+/// let m = matcher.find_at("GET / req-42 200", 0).unwrap();
+/// assert_eq!(Some(10..12), matcher.capture("GET / req-42 200", m, 1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexMatcher {
+    regex: Regex,
+}
+
+impl RegexMatcher {
+    /// Compiles `pattern`. `^` and `$` match at the start and end of every
+    /// line, which only makes a difference in multiline mode.
+    pub fn new(pattern: &str, case_sensitive: bool) -> Result<RegexMatcher, regex::Error> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .multi_line(true)
+            .build()?;
+        Ok(RegexMatcher { regex })
+    }
+
+    /// How many capture groups the pattern has, including group 0.
+    pub fn captures_len(&self) -> usize {
+        self.regex.captures_len()
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        self.regex.find_at(haystack, at).map(|m| m.range())
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.regex.is_match(haystack)
+    }
+
+    fn capture(&self, haystack: &str, m: Range<usize>, group: usize) -> Option<Range<usize>> {
+        // Searching again from the start of the match finds the same match,
+        // with the surrounding text still there for anchors and `\b`.
+        let captures = self.regex.captures_at(haystack, m.start)?;
+        captures.get(group).map(|g| g.range())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_finds_every_match() {
+        let matcher = LiteralMatcher::new("to");
+        let found: Vec<_> = matcher.find_iter("to tell your name to").collect();
+
+        assert_eq!(vec![0..2, 18..20], found);
+    }
+
+    #[test]
+    fn case_insensitive_reports_original_offsets() {
+        let matcher = CaseInsensitiveMatcher::new("straße");
+
+        assert_eq!(Some(4..11), matcher.find_at("Die STRAßE", 0));
+        assert_eq!(None, matcher.find_at("Die STRAßE", 5));
+    }
+
+    #[test]
+    fn empty_query_matches_between_every_char() {
+        let matcher = CaseInsensitiveMatcher::new("");
+        let found: Vec<_> = matcher.find_iter("añ").collect();
+
+        assert_eq!(vec![0..0, 1..1, 3..3], found);
+    }
+
+    #[test]
+    fn regex_captures_keep_their_context() {
+        let matcher = RegexMatcher::new(r"\b(\w)(\w)?\b", true).unwrap();
+        let found: Vec<_> = matcher.find_iter("ab c").collect();
+
+        assert_eq!(vec![0..2, 3..4], found);
+        assert_eq!(Some(1..2), matcher.capture("ab c", 0..2, 2));
+        assert_eq!(None, matcher.capture("ab c", 3..4, 2));
+        assert_eq!(None, LiteralMatcher::new("ab").capture("ab c", 0..2, 1));
+    }
+}
@@ -0,0 +1,139 @@
+//! Proximity search, for `--near A B --within N`.
+use std::ops::Range;
+
+use crate::matcher::Matcher;
+
+/// Matches lines where two words occur within some number of words of each
+/// other, in either order.
+///
+/// Lines are split into words at anything that isn't a letter, a digit or
+/// `_`, and the terms have to match whole words. Every occurrence of either
+/// term that has the other one close enough is a match, so both get
+/// highlighted.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::near::NearMatcher;
+/// use minigrep::Matcher;
+///
+/// let matcher = NearMatcher::new("disk", "full", 2, true);
+/// assert!(matcher.is_match("full, the disk is"));
+/// assert!(matcher.is_match("disk is full"));
+/// assert!(!matcher.is_match("disk is now almost full"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct NearMatcher {
+    a: String,
+    b: String,
+    within: usize,
+    case_sensitive: bool,
+}
+
+impl NearMatcher {
+    pub fn new(a: &str, b: &str, within: usize, case_sensitive: bool) -> NearMatcher {
+        let fold = |term: &str| {
+            if case_sensitive {
+                term.to_string()
+            } else {
+                term.to_lowercase()
+            }
+        };
+        NearMatcher {
+            a: fold(a),
+            b: fold(b),
+            within,
+            case_sensitive,
+        }
+    }
+
+    /// Whether `term` is a single word the way lines get split up, which is
+    /// all a `NearMatcher` can find.
+    pub fn is_word(term: &str) -> bool {
+        let mut words = words(term);
+        words.next() == Some(0..term.len()) && words.next().is_none()
+    }
+
+    /// The ranges of every occurrence of either term that has the other one
+    /// nearby, in order.
+    fn hits(&self, line: &str) -> Vec<Range<usize>> {
+        let words: Vec<_> = words(line)
+            .map(|range| {
+                let word = &line[range.clone()];
+                let word = if self.case_sensitive {
+                    word.to_string()
+                } else {
+                    word.to_lowercase()
+                };
+                (range, word == self.a, word == self.b)
+            })
+            .collect();
+
+        let mut hits = Vec::new();
+        for (i, (range, is_a, is_b)) in words.iter().enumerate() {
+            let nearby = i.saturating_sub(self.within)..words.len().min(i + self.within + 1);
+            let partnered = nearby.filter(|&j| j != i).any(|j| {
+                let (_, a, b) = &words[j];
+                (*is_a && *b) || (*is_b && *a)
+            });
+            if partnered {
+                hits.push(range.clone());
+            }
+        }
+        hits
+    }
+}
+
+impl Matcher for NearMatcher {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        self.hits(haystack).into_iter().find(|hit| hit.start >= at)
+    }
+}
+
+/// Splits `line` into words, returning the byte range of each.
+fn words(line: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut chars = line.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, _) = chars.by_ref().find(|&(_, c)| is_word(c))?;
+        let mut end = line.len();
+        while let Some(&(i, c)) = chars.peek() {
+            if !is_word(c) {
+                end = i;
+                break;
+            }
+            chars.next();
+        }
+        Some(start..end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_lines_into_words() {
+        let line = "a disk_0, (full) é!";
+        let found: Vec<_> = words(line).map(|r| &line[r]).collect();
+        assert_eq!(vec!["a", "disk_0", "full", "é"], found);
+
+        assert!(NearMatcher::is_word("disk_0"));
+        assert!(!NearMatcher::is_word("disk full"));
+        assert!(!NearMatcher::is_word(""));
+    }
+
+    #[test]
+    fn highlights_both_terms_in_either_order() {
+        let matcher = NearMatcher::new("timeout", "DB", 1, false);
+        let line = "db Timeout, then timeout again near the db";
+        let found: Vec<_> = matcher.find_iter(line).map(|r| &line[r]).collect();
+        assert_eq!(vec!["db", "Timeout"], found);
+
+        // Whole words only, and a term can't partner itself.
+        assert!(!matcher.is_match("dbs timeout"));
+        let matcher = NearMatcher::new("ok", "ok", 3, true);
+        assert!(!matcher.is_match("ok then"));
+        assert!(matcher.is_match("ok ok"));
+    }
+}
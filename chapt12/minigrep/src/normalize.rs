@@ -0,0 +1,210 @@
+//! Matching that sees through Unicode normalization, for `--normalize` and
+//! `--ignore-diacritics`.
+//!
+//! Both the query and the text are compatibility-decomposed (NFKD) before
+//! they're compared, which is the same as comparing their NFKC forms. So
+//! `café` finds `café` whether either of them spells the `é` as one
+//! character or as `e` plus a combining accent, and `ﬁ` finds `fi`. Ignoring
+//! diacritics goes one step further and drops the combining marks, so
+//! `resume` finds `résumé`.
+use std::cell::RefCell;
+use std::ops::Range;
+
+use unicode_normalization::char::{
+    canonical_combining_class, decompose_compatible, is_combining_mark,
+};
+
+use crate::matcher::Matcher;
+
+/// Text after decomposing, with a way back to where each byte came from.
+#[derive(Debug, Default)]
+struct Folded {
+    /// The text this was folded from, so it can be reused for the same text.
+    source: String,
+    text: String,
+    /// For every byte of `text`, the range of the character in `source` it
+    /// came from.
+    origins: Vec<Range<usize>>,
+}
+
+/// How text gets folded before comparing.
+#[derive(Debug, Clone, Copy)]
+struct Folding {
+    ignore_diacritics: bool,
+    case_sensitive: bool,
+}
+
+impl Folding {
+    fn fold(self, source: &str) -> Folded {
+        let mut chars = Vec::new();
+        for (i, c) in source.char_indices() {
+            let origin = i..i + c.len_utf8();
+            decompose_compatible(c, |d| chars.push((d, origin.clone())));
+        }
+        // Canonical ordering: runs of combining marks are sorted by class, so
+        // marks typed in a different order still compare equal.
+        let mut run_start = 0;
+        for i in 0..=chars.len() {
+            if chars
+                .get(i)
+                .is_some_and(|(c, _)| canonical_combining_class(*c) != 0)
+            {
+                continue;
+            }
+            chars[run_start..i].sort_by_key(|(c, _)| canonical_combining_class(*c));
+            run_start = i + 1;
+        }
+
+        let mut text = String::with_capacity(source.len());
+        let mut origins = Vec::with_capacity(source.len());
+        let mut push = |c: char, origin: &Range<usize>| {
+            text.push(c);
+            origins.extend((0..c.len_utf8()).map(|_| origin.clone()));
+        };
+        for (c, origin) in &chars {
+            if self.ignore_diacritics && is_combining_mark(*c) {
+                continue;
+            }
+            if self.case_sensitive {
+                push(*c, origin);
+            } else {
+                c.to_lowercase().for_each(|lower| push(lower, origin));
+            }
+        }
+        Folded {
+            source: source.to_string(),
+            text,
+            origins,
+        }
+    }
+}
+
+/// Matches a query against text as if both were normalized, and optionally
+/// with diacritics and case ignored.
+///
+/// Returned ranges point into the original text, and always cover whole
+/// characters of it.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::normalize::NormalizedMatcher;
+/// use minigrep::Matcher;
+///
+/// let nfd = "cafe\u{301} au lait";
+/// let matcher = NormalizedMatcher::new("café", false, true);
+/// assert_eq!(Some(0..6), matcher.find_at(nfd, 0));
+///
+/// let matcher = NormalizedMatcher::new("resume", true, false);
+/// assert!(matcher.is_match("My Résumé.pdf"));
+/// ```
+#[derive(Debug)]
+pub struct NormalizedMatcher {
+    needle: String,
+    folding: Folding,
+    /// The last haystack folded, since the searcher asks about the same one
+    /// again for every match in it.
+    cache: RefCell<Folded>,
+}
+
+impl NormalizedMatcher {
+    pub fn new(needle: &str, ignore_diacritics: bool, case_sensitive: bool) -> NormalizedMatcher {
+        let folding = Folding {
+            ignore_diacritics,
+            case_sensitive,
+        };
+        NormalizedMatcher {
+            needle: folding.fold(needle).text,
+            folding,
+            cache: RefCell::default(),
+        }
+    }
+
+    /// Whether a match at `start..end` of the folded text starts or stops
+    /// partway through a character: inside what one character of the source
+    /// folded to, like the `i` of `ﬁ`, or before a combining mark.
+    fn splits_character(&self, folded: &Folded, start: usize, end: usize) -> bool {
+        let origins = &folded.origins;
+        let inside = |i: usize| i > 0 && i < origins.len() && origins[i - 1] == origins[i];
+        let before_mark = !self.folding.ignore_diacritics
+            && folded.text[end..]
+                .chars()
+                .next()
+                .is_some_and(|c| canonical_combining_class(c) != 0);
+        inside(start) || inside(end) || before_mark
+    }
+}
+
+impl Matcher for NormalizedMatcher {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        if self.needle.is_empty() {
+            return Some(at..at);
+        }
+        let mut cache = self.cache.borrow_mut();
+        if cache.source != haystack {
+            *cache = self.folding.fold(haystack);
+        }
+        let folded = &*cache;
+
+        let mut from = folded.origins.partition_point(|origin| origin.start < at);
+        while let Some(i) = folded.text[from..].find(&self.needle) {
+            let start = from + i;
+            let end = start + self.needle.len();
+            if !self.splits_character(folded, start, end) {
+                return Some(folded.origins[start].start..folded.origins[end - 1].end);
+            }
+            from = start
+                + folded.text[start..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nfc_and_nfd_find_each_other() {
+        let nfc = "Zoë and Zoë";
+        let nfd = "Zoe\u{308} and Zoe\u{308}";
+
+        let matcher = NormalizedMatcher::new(nfd, false, true);
+        assert!(matcher.is_match(nfc));
+        let matcher = NormalizedMatcher::new("Zoë", false, true);
+        let found: Vec<_> = matcher.find_iter(nfd).collect();
+        assert_eq!(vec![0..5, 10..15], found);
+
+        // Without ignoring diacritics, an `e` isn't an `ë`.
+        let matcher = NormalizedMatcher::new("Zoe", false, true);
+        assert!(!matcher.is_match(nfd));
+    }
+
+    #[test]
+    fn compatibility_forms_and_mark_order_are_ignored() {
+        let matcher = NormalizedMatcher::new("file", false, true);
+        assert_eq!(Some(2..7), matcher.find_at("a ﬁle", 0));
+        // Only part of the ligature isn't a match for it.
+        for part in ["i", "f", "il"] {
+            let matcher = NormalizedMatcher::new(part, false, true);
+            assert_eq!(None, matcher.find_at("a ﬁle", 0), "{}", part);
+        }
+
+        // Dot below (class 220) and circumflex (class 230) in either order.
+        let matcher = NormalizedMatcher::new("o\u{323}\u{302}", false, true);
+        assert!(matcher.is_match("o\u{302}\u{323}"));
+        assert!(matcher.is_match("ộ"));
+    }
+
+    #[test]
+    fn ignoring_diacritics_and_case() {
+        let matcher = NormalizedMatcher::new("RESUME", true, false);
+        let text = "see résumé";
+        let m = matcher.find_at(text, 0).unwrap();
+        assert_eq!("résumé", &text[m]);
+        assert_eq!(None, matcher.find_at(text, 5));
+    }
+}
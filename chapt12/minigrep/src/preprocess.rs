@@ -0,0 +1,105 @@
+//! Running files through another program before searching them, for `--pre`.
+//!
+//! The command gets the file's path as its last argument and the file itself
+//! on stdin, and whatever it prints to stdout is what gets searched. That's
+//! handy for decompressing, extracting text from documents or stripping
+//! colour codes out of logs.
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::glob::{GlobError, GlobSet};
+
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    program: String,
+    args: Vec<String>,
+    globs: GlobSet,
+}
+
+impl Preprocessor {
+    /// `command` is split on whitespace into a program and its arguments.
+    /// Anything fancier (quoting, pipes) belongs in a small script.
+    pub fn new(command: &str) -> Option<Preprocessor> {
+        let mut words = command.split_whitespace().map(String::from);
+        Some(Preprocessor {
+            program: words.next()?,
+            args: words.collect(),
+            globs: GlobSet::new(),
+        })
+    }
+
+    /// Only preprocess files matching `pattern` (or, with a leading `!`, not
+    /// matching it). With no globs every file is preprocessed.
+    pub fn glob(&mut self, pattern: &str) -> Result<(), GlobError> {
+        self.globs.add(pattern)
+    }
+
+    pub fn applies_to(&self, path: &Path) -> bool {
+        self.globs.is_match(path)
+    }
+
+    /// Runs the command on `path` and returns what it printed.
+    ///
+    /// Fails if the command can't be started, exits unsuccessfully or prints
+    /// something that isn't UTF-8. Its stderr goes straight to ours.
+    pub fn run(&self, path: &Path) -> io::Result<String> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg(path)
+            .stdin(Stdio::from(File::open(path)?))
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("couldn't run preprocessor {}: {}", self.program, e),
+                )
+            })?;
+
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "preprocessor {} failed: {}",
+                self.program, output.status
+            )));
+        }
+        String::from_utf8(output.stdout).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "preprocessor output was not valid UTF-8",
+            )
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+    use std::fs;
+
+    #[test]
+    fn searches_what_the_command_prints() {
+        let path = temp_path("pre.txt");
+        fs::write(&path, "shout\n").unwrap();
+
+        let pre = Preprocessor::new("sed s/shout/SHOUT/").unwrap();
+        assert_eq!("SHOUT\n", pre.run(&path).unwrap());
+
+        let pre = Preprocessor::new("false").unwrap();
+        assert!(pre.run(&path).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn globs_limit_which_files_are_preprocessed() {
+        let mut pre = Preprocessor::new("pdftotext").unwrap();
+        assert!(pre.applies_to(Path::new("a.txt")));
+
+        pre.glob("*.pdf").unwrap();
+        assert!(pre.applies_to(Path::new("docs/a.pdf")));
+        assert!(!pre.applies_to(Path::new("a.txt")));
+    }
+}
@@ -0,0 +1,629 @@
+//! Writing matches out, either for people to read or in one of a few formats
+//! for other programs: JSON Lines, SARIF for code-scanning tools, and the
+//! quickfix formats vim and Emacs understand.
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::json;
+use crate::matcher::Matcher;
+use crate::searcher::Match;
+use crate::stats::Stats;
+
+/// How matches are written out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// minigrep's usual output: the matching lines, with the file name in
+    /// front when more than one file is being searched.
+    #[default]
+    Standard,
+    /// A `match` event per match, then a `summary` event with the stats.
+    Json,
+    /// A single SARIF 2.1.0 log, written once the search is finished.
+    Sarif,
+    /// `path:line:column:line` for every match, like `:vimgrep`.
+    Vimgrep,
+    /// `path:line:column: line` for every match, like a compiler message, for
+    /// Emacs' compilation mode.
+    Emacs,
+}
+
+impl Format {
+    /// Looks a format up by the name `--format` takes.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "standard" => Some(Format::Standard),
+            "json" => Some(Format::Json),
+            "sarif" => Some(Format::Sarif),
+            "vimgrep" => Some(Format::Vimgrep),
+            "emacs" => Some(Format::Emacs),
+            _ => None,
+        }
+    }
+}
+
+/// Whether to highlight matches with terminal colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Colour when stdout is a terminal and `NO_COLOR` isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Looks a choice up by the name `--color` takes.
+    pub fn from_name(name: &str) -> Option<ColorChoice> {
+        match name {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Settles `Auto` for output going to stdout.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Turns matched text bold red.
+pub(crate) const HIGHLIGHT: &str = "\x1b[1;31m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// A pattern being searched for, and a matcher that finds just that pattern.
+pub type Pattern = (String, Box<dyn Matcher>);
+
+/// Prints matches and keeps [`Stats`] on everything it's shown.
+///
+/// What the output looks like depends on the [`Format`]. JSON and SARIF also
+/// need [`Printer::finish`] to be called at the end, for the summary event and
+/// for the whole SARIF log respectively.
+pub struct Printer<W> {
+    out: W,
+    multiline: bool,
+    with_filename: bool,
+    line_number: bool,
+    column: bool,
+    only_matching: bool,
+    format: Format,
+    null: bool,
+    color: bool,
+    patterns: Vec<Pattern>,
+    /// SARIF results, held back until the log is written by `finish`.
+    results: Vec<String>,
+    stats: Stats,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W) -> Printer<W> {
+        Printer {
+            out,
+            multiline: false,
+            with_filename: false,
+            line_number: false,
+            column: false,
+            only_matching: false,
+            format: Format::Standard,
+            null: false,
+            color: false,
+            patterns: Vec::new(),
+            results: Vec::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Print a `start-end:` header with the line numbers before each match.
+    pub fn multiline(mut self, yes: bool) -> Printer<W> {
+        self.multiline = yes;
+        self
+    }
+
+    /// Put the file name in front of every match.
+    pub fn with_filename(mut self, yes: bool) -> Printer<W> {
+        self.with_filename = yes;
+        self
+    }
+
+    /// Put the line number in front of every match.
+    pub fn line_number(mut self, yes: bool) -> Printer<W> {
+        self.line_number = yes;
+        self
+    }
+
+    /// Put the 1-based byte column of the match in front of every match.
+    pub fn column(mut self, yes: bool) -> Printer<W> {
+        self.column = yes;
+        self
+    }
+
+    /// Print each match on its own line instead of the whole line it's in.
+    pub fn only_matching(mut self, yes: bool) -> Printer<W> {
+        self.only_matching = yes;
+        self
+    }
+
+    /// Highlight the matched parts of lines. Only affects the standard format.
+    pub fn color(mut self, yes: bool) -> Printer<W> {
+        self.color = yes;
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Printer<W> {
+        self.format = format;
+        self
+    }
+
+    /// The patterns being searched for. SARIF output describes each one as a
+    /// rule, and a result belongs to the first rule whose pattern matches all
+    /// of its text.
+    pub fn patterns(mut self, patterns: Vec<Pattern>) -> Printer<W> {
+        self.patterns = patterns;
+        self
+    }
+
+    /// End file names with a NUL byte instead of `:` or a newline, so names
+    /// with odd characters in them survive `xargs -0`.
+    pub fn null(mut self, yes: bool) -> Printer<W> {
+        self.null = yes;
+        self
+    }
+
+    /// Prints just a file name, for `-l` and `-L`.
+    pub fn print_path(&mut self, path: &Path) -> io::Result<()> {
+        write!(self.out, "{}", path.display())?;
+        self.out.write_all(if self.null { b"\0" } else { b"\n" })
+    }
+
+    /// Prints one match found in `path`.
+    ///
+    /// `skipped` is the number of lines that came before the text the match
+    /// was found in, so line numbers still count from the top of the file
+    /// when only part of it was searched.
+    pub fn print(&mut self, path: &Path, m: &Match, skipped: usize) -> io::Result<()> {
+        self.stats.add_match(m);
+        match self.format {
+            Format::Standard => {}
+            Format::Json => return self.print_json(path, m, skipped),
+            Format::Sarif => {
+                self.add_sarif_results(path, m, skipped);
+                return Ok(());
+            }
+            Format::Vimgrep | Format::Emacs => return self.print_quickfix(path, m, skipped),
+        }
+
+        if self.only_matching {
+            for range in m.ranges.iter().filter(|r| !r.is_empty()) {
+                let (line, column) = position(m.text, range.start);
+                self.prefix(path, skipped + m.line_number + line, column)?;
+                let text = &m.text[range.clone()];
+                let whole = 0..text.len();
+                self.highlighted(text, std::slice::from_ref(&whole))?;
+            }
+            return Ok(());
+        }
+
+        if self.multiline {
+            if self.with_filename {
+                self.filename(path)?;
+            }
+            writeln!(
+                self.out,
+                "{}-{}:",
+                skipped + m.line_number,
+                skipped + m.end_line_number
+            )?;
+        } else {
+            let first = m.ranges.first().map_or(0, |r| r.start);
+            self.prefix(path, skipped + m.line_number, first + 1)?;
+        }
+        self.highlighted(m.text, &m.ranges)
+    }
+
+    /// Writes a line of `text`, with `ranges` of it highlighted if colours
+    /// are on.
+    fn highlighted(&mut self, text: &str, ranges: &[std::ops::Range<usize>]) -> io::Result<()> {
+        if !self.color {
+            return writeln!(self.out, "{}", text);
+        }
+        let mut written = 0;
+        for range in ranges {
+            // Overlapping ranges can't be highlighted twice.
+            if range.is_empty() || range.start < written {
+                continue;
+            }
+            write!(
+                self.out,
+                "{}{}{}{}",
+                &text[written..range.start],
+                HIGHLIGHT,
+                &text[range.clone()],
+                RESET
+            )?;
+            written = range.end;
+        }
+        writeln!(self.out, "{}", &text[written..])
+    }
+
+    /// Writes whichever of the file name, line number and column were asked for.
+    fn prefix(&mut self, path: &Path, line_number: usize, column: usize) -> io::Result<()> {
+        if self.with_filename {
+            self.filename(path)?;
+        }
+        if self.line_number {
+            write!(self.out, "{}:", line_number)?;
+        }
+        if self.column {
+            write!(self.out, "{}:", column)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the file name in front of a match.
+    fn filename(&mut self, path: &Path) -> io::Result<()> {
+        write!(self.out, "{}", path.display())?;
+        self.out.write_all(if self.null { b"\0" } else { b":" })
+    }
+
+    fn print_json(&mut self, path: &Path, m: &Match, skipped: usize) -> io::Result<()> {
+        let submatches: Vec<_> = m
+            .ranges
+            .iter()
+            .map(|r| {
+                format!(
+                    r#"{{"match":{},"start":{},"end":{}}}"#,
+                    json::string(&m.text[r.clone()]),
+                    r.start,
+                    r.end
+                )
+            })
+            .collect();
+        writeln!(
+            self.out,
+            r#"{{"type":"match","data":{{"path":{},"line_number":{},"end_line_number":{},"absolute_offset":{},"lines":{},"submatches":[{}]}}}}"#,
+            json::string(&path.to_string_lossy()),
+            skipped + m.line_number,
+            skipped + m.end_line_number,
+            m.offset,
+            json::string(m.text),
+            submatches.join(",")
+        )
+    }
+
+    /// One line per match, each with the whole line it starts on.
+    fn print_quickfix(&mut self, path: &Path, m: &Match, skipped: usize) -> io::Result<()> {
+        for range in &m.ranges {
+            let (line, column) = position(m.text, range.start);
+            let line_start = m.text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = m.text[range.start..]
+                .find('\n')
+                .map_or(m.text.len(), |i| range.start + i);
+            let text = &m.text[line_start..line_end];
+            let line_number = skipped + m.line_number + line;
+            if self.format == Format::Emacs {
+                // Emacs counts columns in characters, not bytes.
+                let column = m.text[line_start..range.start].chars().count() + 1;
+                writeln!(
+                    self.out,
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    line_number,
+                    column,
+                    text
+                )?;
+            } else {
+                writeln!(
+                    self.out,
+                    "{}:{}:{}:{}",
+                    path.display(),
+                    line_number,
+                    column,
+                    text
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Turns each range of a match into a SARIF result with a region.
+    /// Columns are counted in characters, as the log's `columnKind` says.
+    fn add_sarif_results(&mut self, path: &Path, m: &Match, skipped: usize) {
+        let char_column = |at: usize| {
+            let line_start = m.text[..at].rfind('\n').map_or(0, |i| i + 1);
+            m.text[line_start..at].chars().count() + 1
+        };
+        for range in &m.ranges {
+            let (start_line, _) = position(m.text, range.start);
+            let (end_line, _) = position(m.text, range.end);
+            let matched = &m.text[range.clone()];
+            let rule = self
+                .patterns
+                .iter()
+                .position(|(_, matcher)| matcher.find_at(matched, 0) == Some(0..matched.len()))
+                .unwrap_or(0);
+            self.results.push(format!(
+                r#"{{"ruleId":"minigrep/{}","ruleIndex":{},"level":"warning","message":{{"text":{}}},"locations":[{{"physicalLocation":{{"artifactLocation":{{"uri":{}}},"region":{{"startLine":{},"startColumn":{},"endLine":{},"endColumn":{},"snippet":{{"text":{}}}}}}}}}]}}"#,
+                rule,
+                rule,
+                json::string(&format!("Found `{}`", matched)),
+                json::string(&uri(path)),
+                skipped + m.line_number + start_line,
+                char_column(range.start),
+                skipped + m.line_number + end_line,
+                char_column(range.end),
+                json::string(matched)
+            ));
+        }
+    }
+
+    /// Writes the whole SARIF log: one run, with a rule per pattern.
+    fn write_sarif(&mut self) -> io::Result<()> {
+        let rules: Vec<_> = self
+            .patterns
+            .iter()
+            .enumerate()
+            .map(|(i, (pattern, _))| {
+                format!(
+                    r#"{{"id":"minigrep/{}","name":"pattern{}","shortDescription":{{"text":{}}}}}"#,
+                    i,
+                    i,
+                    json::string(&format!("Matches {}", pattern))
+                )
+            })
+            .collect();
+        writeln!(
+            self.out,
+            r#"{{"version":"2.1.0","$schema":"https://json.schemastore.org/sarif-2.1.0.json","runs":[{{"tool":{{"driver":{{"name":"minigrep","version":"{}","rules":[{}]}}}},"columnKind":"unicodeCodePoints","results":[{}]}}]}}"#,
+            env!("CARGO_PKG_VERSION"),
+            rules.join(","),
+            self.results.join(",")
+        )
+    }
+
+    /// Wraps up the run: records how long it took, then writes the JSON
+    /// `summary` event, the SARIF log or, if `show_stats` is set, the
+    /// human-readable report.
+    pub fn finish(&mut self, elapsed: Duration, show_stats: bool) -> io::Result<()> {
+        self.stats.elapsed = elapsed;
+        let stats = &self.stats;
+        if self.format == Format::Sarif {
+            self.write_sarif()?;
+        } else if self.format == Format::Json {
+            let skipped: Vec<_> = stats
+                .files_skipped
+                .iter()
+                .map(|(reason, count)| format!("{}:{}", json::string(reason.key()), count))
+                .collect();
+            writeln!(
+                self.out,
+                r#"{{"type":"summary","data":{{"elapsed_secs":{},"stats":{{"files_searched":{},"files_skipped":{},"skipped_by_reason":{{{}}},"bytes_read":{},{}}}}}}}"#,
+                stats.elapsed.as_secs_f64(),
+                stats.files_searched,
+                stats.total_skipped(),
+                skipped.join(","),
+                stats.bytes_read,
+                if stats.files_only {
+                    format!(r#""files_matched":{}"#, stats.files_matched)
+                } else {
+                    format!(
+                        r#""matched_lines":{},"matches":{}"#,
+                        stats.matched_lines, stats.matches
+                    )
+                }
+            )?;
+        } else if show_stats {
+            write!(self.out, "\n{}", stats)?;
+        }
+        self.out.flush()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    /// The underlying writer, for anything that isn't a match.
+    pub fn out(&mut self) -> &mut W {
+        &mut self.out
+    }
+}
+
+/// Turns a relative path into a relative URI reference, for SARIF. Every
+/// byte but `/` and the ones RFC 3986 calls unreserved is percent-encoded.
+fn uri(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path).to_string_lossy();
+    let mut uri = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'\\' if cfg!(windows) => uri.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            b => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
+
+/// Returns how many lines into `text` byte `at` is, and its 1-based byte
+/// column on that line.
+fn position(text: &str, at: usize) -> (usize, usize) {
+    let before = &text[..at];
+    let line = before.bytes().filter(|&b| b == b'\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, at - line_start + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::SkipReason;
+    use crate::{LiteralMatcher, RegexMatcher, Searcher, SearcherBuilder};
+
+    #[test]
+    fn prefixes_file_names_when_asked() {
+        let matcher = LiteralMatcher::new("two");
+        let m = Searcher::new()
+            .find_iter(&matcher, "one\ntwo")
+            .next()
+            .unwrap();
+
+        let mut printer = Printer::new(Vec::new()).with_filename(true);
+        printer.print(Path::new("a.txt"), &m, 0).unwrap();
+        assert_eq!("a.txt:two\n", String::from_utf8(printer.out).unwrap());
+    }
+
+    #[test]
+    fn null_ends_file_names() {
+        let matcher = LiteralMatcher::new("two");
+        let m = Searcher::new().find_iter(&matcher, "two").next().unwrap();
+
+        let mut printer = Printer::new(Vec::new()).with_filename(true).null(true);
+        printer.print(Path::new("a b.txt"), &m, 0).unwrap();
+        printer.print_path(Path::new("new\nline.txt")).unwrap();
+        assert_eq!(
+            "a b.txt\0two\nnew\nline.txt\0",
+            String::from_utf8(printer.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn colour_highlights_every_range() {
+        let matcher = LiteralMatcher::new("o");
+        let m = Searcher::new().find_iter(&matcher, "foo!").next().unwrap();
+
+        let mut printer = Printer::new(Vec::new()).color(true);
+        printer.print(Path::new("a.txt"), &m, 0).unwrap();
+        assert_eq!(
+            "f\x1b[1;31mo\x1b[0m\x1b[1;31mo\x1b[0m!\n",
+            String::from_utf8(printer.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn multiline_headers_count_skipped_lines() {
+        let matcher = LiteralMatcher::new("one\ntwo");
+        let searcher = SearcherBuilder::new().multiline(true).build();
+        let m = searcher.find_iter(&matcher, "one\ntwo").next().unwrap();
+
+        let mut printer = Printer::new(Vec::new()).multiline(true);
+        printer.print(Path::new("a.txt"), &m, 10).unwrap();
+        assert_eq!(
+            "11-12:\none\ntwo\n",
+            String::from_utf8(printer.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_output_ends_with_a_summary() {
+        let matcher = LiteralMatcher::new("o");
+        let m = Searcher::new()
+            .find_iter(&matcher, "a\nfoo")
+            .next()
+            .unwrap();
+
+        let mut printer = Printer::new(Vec::new()).format(Format::Json);
+        printer.stats_mut().add_searched(5);
+        printer.stats_mut().add_skipped(SkipReason::Unreadable);
+        printer.print(Path::new("a.txt"), &m, 0).unwrap();
+        printer.finish(Duration::from_secs(2), false).unwrap();
+
+        let out = String::from_utf8(printer.out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            r#"{"type":"match","data":{"path":"a.txt","line_number":2,"end_line_number":2,"absolute_offset":2,"lines":"foo","submatches":[{"match":"o","start":1,"end":2},{"match":"o","start":2,"end":3}]}}"#,
+            lines[0]
+        );
+        assert_eq!(
+            r#"{"type":"summary","data":{"elapsed_secs":2,"stats":{"files_searched":1,"files_skipped":1,"skipped_by_reason":{"unreadable":1},"bytes_read":5,"matched_lines":1,"matches":2}}}"#,
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn quickfix_formats_print_every_match() {
+        let matcher = LiteralMatcher::new("é");
+        let m = Searcher::new()
+            .find_iter(&matcher, "café é")
+            .next()
+            .unwrap();
+
+        let mut printer = Printer::new(Vec::new()).format(Format::Vimgrep);
+        printer.print(Path::new("a.txt"), &m, 0).unwrap();
+        assert_eq!(
+            "a.txt:1:4:café é\na.txt:1:7:café é\n",
+            String::from_utf8(printer.out).unwrap()
+        );
+
+        let mut printer = Printer::new(Vec::new()).format(Format::Emacs);
+        printer.print(Path::new("a.txt"), &m, 0).unwrap();
+        assert_eq!(
+            "a.txt:1:4: café é\na.txt:1:6: café é\n",
+            String::from_utf8(printer.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn sarif_log_has_a_rule_per_pattern_and_a_region_per_match() {
+        let matcher = RegexMatcher::new("unwrap|expect", true).unwrap();
+        let m = Searcher::new()
+            .find_iter(&matcher, "let x = y.unwrap().expect(\"z\");")
+            .next()
+            .unwrap();
+
+        let mut printer = Printer::new(Vec::new())
+            .format(Format::Sarif)
+            .patterns(vec![
+                (
+                    "unwrap".to_string(),
+                    Box::new(LiteralMatcher::new("unwrap")),
+                ),
+                (
+                    "expect".to_string(),
+                    Box::new(LiteralMatcher::new("expect")),
+                ),
+            ]);
+        printer.print(Path::new("./src/my lib.rs"), &m, 4).unwrap();
+        assert!(printer.out.is_empty());
+        printer.finish(Duration::from_secs(1), false).unwrap();
+
+        let out = String::from_utf8(printer.out).unwrap();
+        assert!(out.starts_with(r#"{"version":"2.1.0","#));
+        assert!(out.contains(r#""rules":[{"id":"minigrep/0","name":"pattern0","shortDescription":{"text":"Matches unwrap"}},{"id":"minigrep/1","name":"pattern1","shortDescription":{"text":"Matches expect"}}]"#));
+        assert!(out.contains(r#"{"ruleId":"minigrep/0","ruleIndex":0,"level":"warning","message":{"text":"Found `unwrap`"},"locations":[{"physicalLocation":{"artifactLocation":{"uri":"src/my%20lib.rs"},"region":{"startLine":5,"startColumn":11,"endLine":5,"endColumn":17,"#));
+        assert!(out.contains(r#"{"ruleId":"minigrep/1","ruleIndex":1,"level":"warning","message":{"text":"Found `expect`"}"#));
+    }
+
+    #[test]
+    fn sarif_uris_encode_everything_but_unreserved_characters() {
+        assert_eq!("src/a-b_c.~d/e.rs", uri(Path::new("./src/a-b_c.~d/e.rs")));
+        assert_eq!(
+            "caf%C3%A9/%22q%22%20%23%3F%25%5B%5D%0A.rs",
+            uri(Path::new("café/\"q\" #?%[]\n.rs"))
+        );
+    }
+
+    #[test]
+    fn only_matching_prints_each_match_with_its_position() {
+        let matcher = LiteralMatcher::new("o\nb");
+        let searcher = SearcherBuilder::new().multiline(true).build();
+        let m = searcher.find_iter(&matcher, "a\nfoo\nbo").next().unwrap();
+
+        let mut printer = Printer::new(Vec::new())
+            .only_matching(true)
+            .line_number(true)
+            .column(true);
+        printer.print(Path::new("a.txt"), &m, 1).unwrap();
+        assert_eq!("3:3:o\nb\n", String::from_utf8(printer.out).unwrap());
+    }
+}
@@ -0,0 +1,405 @@
+//! The `--query` language: terms combined with `AND`, `OR` and `NOT`.
+//!
+//! ```text
+//! timeout AND db AND NOT retry
+//! (error OR warning) "connection reset"
+//! ```
+//!
+//! Terms are plain words or `"quoted phrases"`, and are matched literally (or
+//! ignoring case) against each line. Terms next to each other are `AND`ed.
+//! `NOT` binds tightest, then `AND`, then `OR`, and parentheses group. The
+//! keywords only count in capitals, so `and` is just a word.
+use std::fmt;
+use std::ops::Range;
+
+use crate::matcher::Matcher;
+
+/// A parsed query. Leaves are terms, which start out as strings and become
+/// matchers in a [`QueryMatcher`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<T = String> {
+    Term(T),
+    Not(Box<Expr<T>>),
+    And(Box<Expr<T>>, Box<Expr<T>>),
+    Or(Box<Expr<T>>, Box<Expr<T>>),
+}
+
+impl<T> Expr<T> {
+    /// Swaps every term for `f(term)`, keeping the shape of the tree.
+    pub fn map<U>(self, f: &mut impl FnMut(T) -> U) -> Expr<U> {
+        match self {
+            Expr::Term(t) => Expr::Term(f(t)),
+            Expr::Not(e) => Expr::Not(Box::new(e.map(f))),
+            Expr::And(a, b) => Expr::And(Box::new(a.map(f)), Box::new(b.map(f))),
+            Expr::Or(a, b) => Expr::Or(Box::new(a.map(f)), Box::new(b.map(f))),
+        }
+    }
+
+    /// Evaluates the expression, with `f` saying whether each term is there.
+    pub fn eval(&self, f: &impl Fn(&T) -> bool) -> bool {
+        match self {
+            Expr::Term(t) => f(t),
+            Expr::Not(e) => !e.eval(f),
+            Expr::And(a, b) => a.eval(f) && b.eval(f),
+            Expr::Or(a, b) => a.eval(f) || b.eval(f),
+        }
+    }
+
+    /// The terms that count for a match rather than against one, i.e. the
+    /// ones under an even number of `NOT`s. These are what gets highlighted.
+    pub fn positive_terms(&self) -> Vec<&T> {
+        let mut terms = Vec::new();
+        self.collect_terms(true, &mut terms);
+        terms
+    }
+
+    fn collect_terms<'a>(&'a self, positive: bool, terms: &mut Vec<&'a T>) {
+        match self {
+            Expr::Term(t) if positive => terms.push(t),
+            Expr::Term(_) => {}
+            Expr::Not(e) => e.collect_terms(!positive, terms),
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.collect_terms(positive, terms);
+                b.collect_terms(positive, terms);
+            }
+        }
+    }
+}
+
+/// A query that couldn't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Byte offset in the query where things went wrong.
+    pub at: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad --query at byte {}: {}", self.at, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Parses a query into an expression tree.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::query::{parse, Expr};
+///
+/// let term = |t: &str| Box::new(Expr::Term(t.to_string()));
+/// assert_eq!(
+///     Expr::And(term("timeout"), Box::new(Expr::Not(term("retry")))),
+///     parse("timeout AND NOT retry").unwrap()
+/// );
+/// ```
+pub fn parse(query: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(query)?;
+    let mut parser = Parser {
+        tokens,
+        next: 0,
+        end: query.len(),
+    };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err(parser.error("unexpected )")),
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push((start, if c == '(' { Token::Open } else { Token::Close }));
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    let c = match chars.next() {
+                        Some((_, '\\')) => chars.next(),
+                        Some((_, '"')) => break,
+                        c => c,
+                    };
+                    match c {
+                        Some((_, c)) => phrase.push(c),
+                        None => {
+                            return Err(ParseError {
+                                at: start,
+                                reason: "unclosed quote",
+                            })
+                        }
+                    }
+                }
+                if phrase.is_empty() {
+                    return Err(ParseError {
+                        at: start,
+                        reason: "empty phrase",
+                    });
+                }
+                tokens.push((start, Token::Phrase(phrase)));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                };
+                tokens.push((start, token));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Where the query ends, for errors about running out of tokens.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn error(&self, reason: &'static str) -> ParseError {
+        let at = self.tokens.get(self.next).map_or(self.end, |(at, _)| *at);
+        ParseError { at, reason }
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.next += 1,
+                // Two terms in a row are ANDed too.
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek().cloned();
+        match token {
+            Some(Token::Word(term) | Token::Phrase(term)) => {
+                self.next += 1;
+                Ok(Expr::Term(term))
+            }
+            Some(Token::Open) => {
+                self.next += 1;
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(self.error("missing )"));
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            Some(_) => Err(self.error("expected a term")),
+            None => Err(self.error("query ended early")),
+        }
+    }
+}
+
+/// Matches lines where a query expression holds.
+///
+/// The matches reported are where the terms that count towards the query
+/// were found. A line that only matches because of a `NOT` gets a single
+/// empty match at its start.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::query::{parse, QueryMatcher};
+/// use minigrep::{LiteralMatcher, Matcher};
+///
+/// let expr = parse("timeout db NOT retry").unwrap();
+/// let matcher = QueryMatcher::new(expr, |term| LiteralMatcher::new(&term));
+/// assert!(matcher.is_match("db timeout after 30s"));
+/// assert!(!matcher.is_match("db timeout, retry 1"));
+/// ```
+pub struct QueryMatcher<M> {
+    expr: Expr<usize>,
+    terms: Vec<M>,
+    /// Indexes into `terms` of the positive ones.
+    positive: Vec<usize>,
+}
+
+impl<M: Matcher> QueryMatcher<M> {
+    /// Builds a matcher for every term with `new_matcher`.
+    pub fn new(expr: Expr, mut new_matcher: impl FnMut(String) -> M) -> QueryMatcher<M> {
+        let mut terms = Vec::new();
+        let expr = expr.map(&mut |term| {
+            terms.push(new_matcher(term));
+            terms.len() - 1
+        });
+        let mut positive: Vec<usize> = expr.positive_terms().into_iter().copied().collect();
+        positive.sort_unstable();
+        positive.dedup();
+        QueryMatcher {
+            expr,
+            terms,
+            positive,
+        }
+    }
+}
+
+impl<M: Matcher> Matcher for QueryMatcher<M> {
+    fn find_at(&self, haystack: &str, at: usize) -> Option<Range<usize>> {
+        if !self.is_match(haystack) {
+            return None;
+        }
+        let found = self
+            .positive
+            .iter()
+            .filter_map(|&i| self.terms[i].find_at(haystack, at))
+            .min_by_key(|m| (m.start, std::cmp::Reverse(m.end)));
+        match found {
+            Some(m) => Some(m),
+            None if at == 0 => Some(0..0),
+            None => None,
+        }
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.expr.eval(&|&i| self.terms[i].is_match(haystack))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiteralMatcher;
+
+    fn term(t: &str) -> Box<Expr> {
+        Box::new(Expr::Term(t.to_string()))
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_and_and_than_or() {
+        assert_eq!(
+            Expr::Or(
+                term("a"),
+                Box::new(Expr::And(term("b"), Box::new(Expr::Not(term("c")))))
+            ),
+            parse("a OR b AND NOT c").unwrap()
+        );
+        assert_eq!(
+            Expr::And(
+                Box::new(Expr::Or(term("a"), term("b"))),
+                term("connection reset")
+            ),
+            parse(r#"(a OR b) "connection reset""#).unwrap()
+        );
+        assert_eq!(
+            Expr::And(term("and"), term("OR")),
+            parse(r#"and "OR""#).unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        let error = |query| parse(query).unwrap_err();
+        assert_eq!(
+            ParseError {
+                at: 0,
+                reason: "unclosed quote"
+            },
+            error("\"oops")
+        );
+        assert_eq!(
+            ParseError {
+                at: 4,
+                reason: "missing )"
+            },
+            error("(a b")
+        );
+        assert_eq!(
+            ParseError {
+                at: 4,
+                reason: "unexpected )"
+            },
+            error("a b ) c")
+        );
+        assert_eq!(
+            ParseError {
+                at: 6,
+                reason: "query ended early"
+            },
+            error("a AND ")
+        );
+        assert_eq!(
+            ParseError {
+                at: 5,
+                reason: "expected a term"
+            },
+            error("a OR OR b")
+        );
+    }
+
+    #[test]
+    fn reports_only_positive_terms() {
+        let expr = parse("timeout AND (db OR cache) AND NOT retry").unwrap();
+        let matcher = QueryMatcher::new(expr, |t| LiteralMatcher::new(&t));
+
+        let line = "cache timeout, db down";
+        let found: Vec<_> = matcher.find_iter(line).map(|r| &line[r]).collect();
+        assert_eq!(vec!["cache", "timeout", "db"], found);
+        assert_eq!(0, matcher.find_iter("db timeout, retry").count());
+
+        let matcher = QueryMatcher::new(parse("NOT retry").unwrap(), |t| LiteralMatcher::new(&t));
+        assert_eq!(
+            vec![0..0],
+            matcher.find_iter("all good").collect::<Vec<_>>()
+        );
+    }
+}
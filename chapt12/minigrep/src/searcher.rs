@@ -0,0 +1,346 @@
+//! The searcher walks a haystack line by line (or all at once in multiline
+//! mode) and hands every hit from a [`Matcher`] over as a [`Match`].
+//!
+//! Results come out lazily through [`Searcher::find_iter`], or get pushed into
+//! a [`Sink`] with [`Searcher::search`], so nothing has to be collected into a
+//! `Vec` unless you want it to be.
+use std::ops::Range;
+
+use crate::matcher::{FindIter, Matcher};
+
+/// One or more lines that contain a match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'h> {
+    /// 1-based number of the first line in `text`.
+    pub line_number: usize,
+    /// 1-based number of the last line in `text`. Only differs from
+    /// `line_number` in multiline mode.
+    pub end_line_number: usize,
+    /// Byte offset of `text` inside the haystack.
+    pub offset: usize,
+    /// The matching lines, without the final line terminator.
+    pub text: &'h str,
+    /// Byte ranges of each match, relative to the start of `text`.
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl<'h> Match<'h> {
+    /// Iterates over the individual lines in `text`.
+    pub fn lines(&self) -> std::str::Lines<'h> {
+        self.text.lines()
+    }
+}
+
+/// Receives matches as the [`Searcher`] finds them.
+///
+/// Any `FnMut(&Match) -> Result<bool, E>` closure is a sink too.
+pub trait Sink {
+    type Error;
+
+    /// Called once for every match. Return `Ok(false)` to stop searching.
+    fn matched(&mut self, m: &Match<'_>) -> Result<bool, Self::Error>;
+}
+
+impl<F, E> Sink for F
+where
+    F: FnMut(&Match<'_>) -> Result<bool, E>,
+{
+    type Error = E;
+
+    fn matched(&mut self, m: &Match<'_>) -> Result<bool, E> {
+        self(m)
+    }
+}
+
+/// Sets up a [`Searcher`].
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::{LiteralMatcher, SearcherBuilder};
+///
+/// let searcher = SearcherBuilder::new().multiline(true).build();
+/// let matcher = LiteralMatcher::new("productive.\nPick");
+/// let contents = "\
+/// Rust:
+/// safe, fast, productive.
+/// Pick three.";
+///
+/// let m = searcher.find_iter(&matcher, contents).next().unwrap();
+/// assert_eq!((2, 3), (m.line_number, m.end_line_number));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SearcherBuilder {
+    multiline: bool,
+    capture: Option<usize>,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder::default()
+    }
+
+    /// Match against the whole haystack at once instead of line by line, so
+    /// matches may span line breaks.
+    pub fn multiline(&mut self, yes: bool) -> &mut SearcherBuilder {
+        self.multiline = yes;
+        self
+    }
+
+    /// Report only capture group `group` of each match instead of the whole
+    /// thing. Matches where the group didn't take part are dropped.
+    pub fn capture(&mut self, group: Option<usize>) -> &mut SearcherBuilder {
+        self.capture = group;
+        self
+    }
+
+    pub fn build(&self) -> Searcher {
+        Searcher {
+            multiline: self.multiline,
+            capture: self.capture,
+        }
+    }
+}
+
+/// Runs a [`Matcher`] over a haystack.
+#[derive(Debug, Clone, Default)]
+pub struct Searcher {
+    multiline: bool,
+    capture: Option<usize>,
+}
+
+impl Searcher {
+    /// A line-by-line searcher with the default options.
+    pub fn new() -> Searcher {
+        SearcherBuilder::new().build()
+    }
+
+    pub fn multiline(&self) -> bool {
+        self.multiline
+    }
+
+    /// Lazily yields every match of `matcher` in `haystack`.
+    pub fn find_iter<'s, 'h, M: Matcher>(
+        &'s self,
+        matcher: &'s M,
+        haystack: &'h str,
+    ) -> Matches<'s, 'h, M> {
+        let mode = if self.multiline {
+            Mode::Multiline {
+                finder: matcher.find_iter(haystack),
+                peeked: None,
+                counted: (0, 1),
+            }
+        } else {
+            Mode::Lines { next: 0, line: 1 }
+        };
+        Matches {
+            matcher,
+            haystack,
+            capture: self.capture,
+            mode,
+        }
+    }
+
+    /// Feeds every match of `matcher` in `haystack` to `sink`, stopping early
+    /// if the sink asks to.
+    pub fn search<M: Matcher, S: Sink>(
+        &self,
+        matcher: &M,
+        haystack: &str,
+        sink: &mut S,
+    ) -> Result<(), S::Error> {
+        for m in self.find_iter(matcher, haystack) {
+            if !sink.matched(&m)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the matches in a haystack, created by [`Searcher::find_iter`].
+pub struct Matches<'s, 'h, M> {
+    matcher: &'s M,
+    haystack: &'h str,
+    capture: Option<usize>,
+    mode: Mode<'s, 'h, M>,
+}
+
+enum Mode<'s, 'h, M> {
+    Lines {
+        next: usize,
+        line: usize,
+    },
+    Multiline {
+        finder: FindIter<'s, 'h, M>,
+        peeked: Option<Range<usize>>,
+        /// A byte offset that starts a line, and that line's number.
+        counted: (usize, usize),
+    },
+}
+
+impl<'h, M: Matcher> Iterator for Matches<'_, 'h, M> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        let haystack = self.haystack;
+        let matcher = self.matcher;
+        let capture = self.capture;
+        // Swaps a match for the capture group we were asked for, if any.
+        let select = |text: &str, m: Range<usize>| match capture {
+            Some(group) => matcher.capture(text, m, group),
+            None => Some(m),
+        };
+        match &mut self.mode {
+            Mode::Lines { next, line } => {
+                while *next < haystack.len() {
+                    let start = *next;
+                    let end = line_end(haystack, start);
+                    let text = trim_line(&haystack[start..end]);
+                    let line_number = *line;
+                    *next = end + 1;
+                    *line += 1;
+
+                    let ranges: Vec<_> = matcher
+                        .find_iter(text)
+                        .filter_map(|m| select(text, m))
+                        .collect();
+                    if !ranges.is_empty() {
+                        return Some(Match {
+                            line_number,
+                            end_line_number: line_number,
+                            offset: start,
+                            text,
+                            ranges,
+                        });
+                    }
+                }
+                None
+            }
+            Mode::Multiline {
+                finder,
+                peeked,
+                counted,
+            } => {
+                let mut found = peeked
+                    .take()
+                    .into_iter()
+                    .chain(finder.by_ref().filter_map(|m| select(haystack, m)));
+                let first = loop {
+                    let m = found.next()?;
+                    // An empty match after the final newline isn't on any line.
+                    if m.start < haystack.len()
+                        || !(haystack.is_empty() || haystack.ends_with('\n'))
+                    {
+                        break m;
+                    }
+                };
+
+                let start = line_start(haystack, first.start);
+                let mut end = line_end(haystack, last_byte(&first));
+                let mut ranges = vec![first];
+                for m in found {
+                    if m.start > end {
+                        *peeked = Some(m);
+                        break;
+                    }
+                    end = end.max(line_end(haystack, last_byte(&m)));
+                    ranges.push(m);
+                }
+
+                let text = trim_line(&haystack[start..end]);
+                let (counted_at, counted_line) = *counted;
+                let line_number = counted_line + newlines(&haystack[counted_at..start]);
+                let end_line_number = line_number + newlines(text);
+                *counted = (start, line_number);
+
+                for range in ranges.iter_mut() {
+                    range.start -= start;
+                    range.end = (range.end - start).min(text.len());
+                }
+                Some(Match {
+                    line_number,
+                    end_line_number,
+                    offset: start,
+                    text,
+                    ranges,
+                })
+            }
+        }
+    }
+}
+
+fn line_start(haystack: &str, at: usize) -> usize {
+    haystack[..at].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Returns the offset of the `\n` ending the line that contains `at`, or the
+/// end of the haystack.
+fn line_end(haystack: &str, at: usize) -> usize {
+    haystack[at..].find('\n').map_or(haystack.len(), |i| at + i)
+}
+
+fn last_byte(m: &Range<usize>) -> usize {
+    if m.end > m.start {
+        m.end - 1
+    } else {
+        m.start
+    }
+}
+
+fn trim_line(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+fn newlines(text: &str) -> usize {
+    text.bytes().filter(|&b| b == b'\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{CaseInsensitiveMatcher, LiteralMatcher};
+
+    const POEM: &str = "\
+I'm nobody! Who are you?\r
+Are you nobody, too?
+Then there's a pair of us - don't tell!";
+
+    #[test]
+    fn lines_report_numbers_offsets_and_ranges() {
+        let matcher = CaseInsensitiveMatcher::new("YOU");
+        let found: Vec<_> = Searcher::new().find_iter(&matcher, POEM).collect();
+
+        assert_eq!(2, found.len());
+        assert_eq!("I'm nobody! Who are you?", found[0].text);
+        assert_eq!(vec![20..23], found[0].ranges);
+        assert_eq!(2, found[1].line_number);
+        assert_eq!(26, found[1].offset);
+    }
+
+    #[test]
+    fn multiline_matches_end_on_the_line_with_their_last_byte() {
+        let searcher = SearcherBuilder::new().multiline(true).build();
+        let matcher = LiteralMatcher::new("too?\n");
+        let found: Vec<_> = searcher.find_iter(&matcher, POEM).collect();
+
+        assert_eq!(1, found.len());
+        assert_eq!((2, 2), (found[0].line_number, found[0].end_line_number));
+        assert_eq!(vec![16..20], found[0].ranges);
+    }
+
+    #[test]
+    fn sink_can_stop_the_search() {
+        let matcher = LiteralMatcher::new("o");
+        let mut seen = 0;
+        Searcher::new()
+            .search(&matcher, POEM, &mut |_: &Match| -> Result<bool, ()> {
+                seen += 1;
+                Ok(false)
+            })
+            .unwrap();
+
+        assert_eq!(1, seen);
+    }
+}
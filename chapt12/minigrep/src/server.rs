@@ -0,0 +1,390 @@
+//! `minigrep --server`: a long-running process that answers searches over
+//! JSON-RPC 2.0, so an editor doesn't have to start minigrep for every
+//! keystroke.
+//!
+//! Messages are one JSON object per line, read from stdin, and everything sent
+//! back is one per line on stdout. A `search` request looks like
+//!
+//! ```text
+//! {"jsonrpc":"2.0","id":1,"method":"search","params":{"query":"TODO","paths":["src"]}}
+//! ```
+//!
+//! with optional `regex`, `ignoreCase` and `multiline` booleans in `params`.
+//! Each match is sent as a `search/match` notification carrying the request's
+//! id as soon as it's found, and the response at the end has the totals. A
+//! `$/cancel` notification with `{"id": 1}` as its params stops that search,
+//! which is then answered with a `RequestCancelled` error. `shutdown` and
+//! `exit` work like they do in the Language Server Protocol.
+//!
+//! Which files are under each searched directory is remembered between
+//! requests. A cached listing is only used again if none of the directories
+//! in it have been modified since.
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::input::{self, MmapChoice};
+use crate::json::{self, object, Value};
+use crate::matcher::{CaseInsensitiveMatcher, LiteralMatcher, Matcher, RegexMatcher};
+use crate::searcher::{Match, SearcherBuilder};
+use crate::walk::Walk;
+
+/// JSON-RPC error codes.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The code the Language Server Protocol uses for cancelled requests.
+pub const REQUEST_CANCELLED: i64 = -32800;
+
+/// Cancellation flags for requests that haven't been answered yet, keyed by
+/// their id written out as JSON.
+type Pending = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+
+/// Something read from the input.
+enum Incoming {
+    Message {
+        message: Value,
+        cancelled: Arc<AtomicBool>,
+    },
+    Invalid(json::ParseError),
+}
+
+/// Answers requests from `input` on `output` until the input ends or an
+/// `exit` notification arrives.
+///
+/// Messages are read on a separate thread, so a `$/cancel` takes effect while
+/// a search is still running.
+pub fn serve<R, W>(input: R, output: W) -> io::Result<()>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    let pending = Pending::default();
+    let (tx, rx) = mpsc::channel();
+    let reader_pending = Arc::clone(&pending);
+    thread::spawn(move || read_messages(input, tx, reader_pending));
+
+    let mut server = Server::new(output);
+    for incoming in rx {
+        match incoming {
+            Incoming::Invalid(e) => server.error(Value::Null, PARSE_ERROR, &e.to_string())?,
+            Incoming::Message { message, cancelled } => {
+                let keep_going = server.handle(&message, &cancelled)?;
+                if let Some(id) = message.get("id") {
+                    pending.lock().unwrap().remove(&id.to_string());
+                }
+                if !keep_going {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads messages until the input ends, flagging cancelled requests straight
+/// away and passing everything else on.
+fn read_messages(input: impl BufRead, tx: Sender<Incoming>, pending: Pending) {
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message = match json::parse(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                if tx.send(Incoming::Invalid(e)).is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let mut pending = pending.lock().unwrap();
+        if message.get("method").and_then(Value::as_str) == Some("$/cancel") {
+            let id = message.get("params").and_then(|p| p.get("id"));
+            if let Some(flag) = id.and_then(|id| pending.get(&id.to_string())) {
+                flag.store(true, Ordering::Relaxed);
+            }
+            continue;
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Some(id) = message.get("id") {
+            pending.insert(id.to_string(), Arc::clone(&cancelled));
+        }
+        drop(pending);
+        if tx.send(Incoming::Message { message, cancelled }).is_err() {
+            break;
+        }
+    }
+}
+
+/// The files found under one searched path, and the directories that were
+/// read to find them.
+struct Listing {
+    files: Vec<PathBuf>,
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Listing {
+    fn take(path: &str) -> Listing {
+        let mut walk = Walk::new([path]).record_dirs(true);
+        let files = walk.by_ref().filter_map(Result::ok).collect();
+        Listing {
+            files,
+            dirs: walk.dirs().to_vec(),
+        }
+    }
+
+    /// Whether walking again would still find the same files.
+    fn is_fresh(&self) -> bool {
+        self.dirs
+            .iter()
+            .all(|(dir, modified)| fs::metadata(dir).and_then(|m| m.modified()).ok() == *modified)
+    }
+}
+
+/// Handles one message at a time, writing responses and notifications to
+/// `out`.
+pub struct Server<W> {
+    out: W,
+    listings: HashMap<String, Listing>,
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(out: W) -> Server<W> {
+        Server {
+            out,
+            listings: HashMap::new(),
+        }
+    }
+
+    /// Handles a request or notification. Returns `false` once it's time to
+    /// exit.
+    ///
+    /// `cancelled` can be set from another thread to stop a search early.
+    pub fn handle(&mut self, message: &Value, cancelled: &AtomicBool) -> io::Result<bool> {
+        let id = message.get("id").cloned();
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").unwrap_or(&Value::Null);
+        match (method, id) {
+            (Some("exit"), _) => return Ok(false),
+            (Some("search"), Some(id)) => self.search(id, params, cancelled)?,
+            (Some("shutdown"), Some(id)) => {
+                self.listings.clear();
+                self.respond(id, Value::Null)?;
+            }
+            (Some(method), Some(id)) => {
+                self.error(id, METHOD_NOT_FOUND, &format!("unknown method {}", method))?
+            }
+            // Notifications we don't know about are ignored, as the spec says.
+            (Some(_), None) => {}
+            (None, id) => self.error(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "message has no method",
+            )?,
+        }
+        Ok(true)
+    }
+
+    fn search(&mut self, id: Value, params: &Value, cancelled: &AtomicBool) -> io::Result<()> {
+        let Some(query) = params.get("query").and_then(Value::as_str) else {
+            return self.error(id, INVALID_PARAMS, "search needs a query");
+        };
+        let paths: Vec<String> = match params.get("paths") {
+            None => vec![".".to_string()],
+            Some(paths) => match paths
+                .as_array()
+                .and_then(|paths| paths.iter().map(|p| p.as_str().map(String::from)).collect())
+            {
+                Some(paths) => paths,
+                None => return self.error(id, INVALID_PARAMS, "paths must be a list of strings"),
+            },
+        };
+        let flag = |name| params.get(name).and_then(Value::as_bool).unwrap_or(false);
+        let case_sensitive = !flag("ignoreCase");
+        let matcher: Box<dyn Matcher> = if flag("regex") {
+            match RegexMatcher::new(query, case_sensitive) {
+                Ok(matcher) => Box::new(matcher),
+                Err(e) => return self.error(id, INVALID_PARAMS, &e.to_string()),
+            }
+        } else if case_sensitive {
+            Box::new(LiteralMatcher::new(query))
+        } else {
+            Box::new(CaseInsensitiveMatcher::new(query))
+        };
+        let searcher = SearcherBuilder::new().multiline(flag("multiline")).build();
+
+        let mut files_searched = 0;
+        let mut matches = 0;
+        for path in &paths {
+            for file in self.files(path) {
+                if cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Ok(contents) = input::read(&file, MmapChoice::Auto) else {
+                    continue;
+                };
+                files_searched += 1;
+                let path = Value::from(file.to_string_lossy().into_owned());
+                searcher.search(&matcher, &contents, &mut |m: &Match| -> io::Result<bool> {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return Ok(false);
+                    }
+                    matches += 1;
+                    self.notify("search/match", match_params(&id, &path, m))?;
+                    Ok(true)
+                })?;
+            }
+        }
+
+        if cancelled.load(Ordering::Relaxed) {
+            return self.error(id, REQUEST_CANCELLED, "search cancelled");
+        }
+        self.respond(
+            id,
+            object([
+                ("matches", matches.into()),
+                ("files_searched", files_searched.into()),
+            ]),
+        )
+    }
+
+    /// The files under `path`, from the cache if it's still good.
+    fn files(&mut self, path: &str) -> Vec<PathBuf> {
+        let fresh = self.listings.get(path).is_some_and(Listing::is_fresh);
+        if !fresh {
+            self.listings.insert(path.to_string(), Listing::take(path));
+        }
+        self.listings[path].files.clone()
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        writeln!(self.out, "{}", message)?;
+        self.out.flush()
+    }
+
+    fn respond(&mut self, id: Value, result: Value) -> io::Result<()> {
+        self.send(object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("result", result),
+        ]))
+    }
+
+    fn error(&mut self, id: Value, code: i64, message: &str) -> io::Result<()> {
+        let error = object([
+            ("code", Value::Number(code as f64)),
+            ("message", message.into()),
+        ]);
+        self.send(object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("error", error),
+        ]))
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(object([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ]))
+    }
+}
+
+/// The params of a `search/match` notification.
+fn match_params(id: &Value, path: &Value, m: &Match) -> Value {
+    let submatches = m
+        .ranges
+        .iter()
+        .map(|r| object([("start", r.start.into()), ("end", r.end.into())]))
+        .collect();
+    object([
+        ("id", id.clone()),
+        ("path", path.clone()),
+        ("line_number", m.line_number.into()),
+        ("end_line_number", m.end_line_number.into()),
+        ("text", m.text.into()),
+        ("submatches", Value::Array(submatches)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    #[test]
+    fn streams_matches_then_answers() {
+        let dir = temp_dir("server");
+        fs::write(dir.join("a.txt"), "one\nTODO: two\n").unwrap();
+        let search = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"search","params":{{"query":"todo","ignoreCase":true,"paths":[{}]}}}}"#,
+            json::string(&dir.to_string_lossy())
+        );
+        let input = format!("{}\nnot json\n{{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"nope\"}}\n{{\"jsonrpc\":\"2.0\",\"method\":\"exit\"}}\n{}\n", search, search);
+
+        let mut out = Vec::new();
+        serve(io::Cursor::new(input), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(4, lines.len(), "{}", out);
+        assert_eq!(
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"search/match","params":{{"id":1,"path":{},"line_number":2,"end_line_number":2,"text":"TODO: two","submatches":[{{"start":0,"end":4}}]}}}}"#,
+                json::string(&dir.join("a.txt").to_string_lossy())
+            ),
+            lines[0]
+        );
+        assert_eq!(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"matches":1,"files_searched":1}}"#,
+            lines[1]
+        );
+        assert!(lines[2].starts_with(r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"#));
+        assert_eq!(
+            r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32601,"message":"unknown method nope"}}"#,
+            lines[3]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_searches_get_an_error() {
+        let mut server = Server::new(Vec::new());
+        let request = json::parse(r#"{"id":"a","method":"search","params":{"query":"x"}}"#);
+        let cancelled = AtomicBool::new(true);
+        server.handle(&request.unwrap(), &cancelled).unwrap();
+        assert_eq!(
+            "{\"jsonrpc\":\"2.0\",\"id\":\"a\",\"error\":{\"code\":-32800,\"message\":\"search cancelled\"}}\n",
+            String::from_utf8(server.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn listings_are_cached_until_a_directory_changes() {
+        let dir = temp_dir("server-cache");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a.txt"), "").unwrap();
+        let path = dir.to_string_lossy().into_owned();
+
+        let mut server = Server::new(io::sink());
+        assert_eq!(1, server.files(&path).len());
+        assert!(server.listings[&path].is_fresh());
+
+        fs::write(dir.join("sub/b.txt"), "").unwrap();
+        assert!(!server.listings[&path].is_fresh());
+        assert_eq!(2, server.files(&path).len());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+//! Counting what a search actually did, for `--stats`.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::searcher::Match;
+
+/// Why a file wasn't searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// It couldn't be opened or read.
+    Unreadable,
+    /// It wasn't valid UTF-8.
+    NotUtf8,
+    /// It was bigger than `--max-filesize`.
+    TooLarge,
+    /// The trigram index showed it couldn't match.
+    Index,
+}
+
+impl SkipReason {
+    /// A short identifier, used as the key in JSON output.
+    pub fn key(&self) -> &'static str {
+        match self {
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::NotUtf8 => "not_utf8",
+            SkipReason::TooLarge => "too_large",
+            SkipReason::Index => "index",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::NotUtf8 => "not valid UTF-8",
+            SkipReason::TooLarge => "over the size limit",
+            SkipReason::Index => "ruled out by the index",
+        })
+    }
+}
+
+/// Totals for a whole run.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    pub files_searched: usize,
+    pub files_skipped: BTreeMap<SkipReason, usize>,
+    pub bytes_read: u64,
+    pub matched_lines: usize,
+    pub matches: usize,
+    /// Only files with a match are counted, in `files_matched`, and not the
+    /// lines and matches in them. That's how `-l` and `-L` count, since they
+    /// stop reading a file at its first match.
+    pub files_only: bool,
+    pub files_matched: usize,
+    pub elapsed: Duration,
+}
+
+impl Stats {
+    /// Records a file (or chunk of one) that was read and searched.
+    pub fn add_searched(&mut self, bytes: usize) {
+        self.files_searched += 1;
+        self.bytes_read += bytes as u64;
+    }
+
+    pub fn add_skipped(&mut self, reason: SkipReason) {
+        *self.files_skipped.entry(reason).or_default() += 1;
+    }
+
+    /// Counts a match. One with no ranges is only there for context, like a
+    /// CSV header, and doesn't count.
+    pub fn add_match(&mut self, m: &Match) {
+        if m.ranges.is_empty() {
+            return;
+        }
+        self.matched_lines += m.end_line_number - m.line_number + 1;
+        self.matches += m.ranges.len();
+    }
+
+    /// Counts a file with at least one match, under [`Stats::files_only`].
+    pub fn add_file_matched(&mut self) {
+        self.files_matched += 1;
+    }
+
+    pub fn total_skipped(&self) -> usize {
+        self.files_skipped.values().sum()
+    }
+}
+
+impl fmt::Display for Stats {
+    /// The human-readable report printed by `--stats`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.files_only {
+            writeln!(
+                f,
+                "{} files matched (lines and matches not counted)",
+                self.files_matched
+            )?;
+        } else {
+            writeln!(f, "{} matched lines", self.matched_lines)?;
+            writeln!(f, "{} matches", self.matches)?;
+        }
+        writeln!(f, "{} files searched", self.files_searched)?;
+        write!(f, "{} files skipped", self.total_skipped())?;
+        if !self.files_skipped.is_empty() {
+            let reasons: Vec<_> = self
+                .files_skipped
+                .iter()
+                .map(|(reason, count)| format!("{} {}", count, reason))
+                .collect();
+            write!(f, " ({})", reasons.join(", "))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{} bytes read", self.bytes_read)?;
+        writeln!(f, "{:.6} seconds", self.elapsed.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_breaks_down_skipped_files() {
+        let mut stats = Stats::default();
+        stats.add_searched(120);
+        stats.add_searched(30);
+        stats.add_skipped(SkipReason::NotUtf8);
+        stats.add_skipped(SkipReason::Index);
+        stats.add_skipped(SkipReason::Index);
+        stats.elapsed = Duration::from_millis(1500);
+
+        assert_eq!(
+            "\
+0 matched lines
+0 matches
+2 files searched
+3 files skipped (1 not valid UTF-8, 2 ruled out by the index)
+150 bytes read
+1.500000 seconds
+",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn listing_files_counts_files_instead_of_matches() {
+        let stats = Stats {
+            files_only: true,
+            files_matched: 2,
+            ..Stats::default()
+        };
+        assert!(stats
+            .to_string()
+            .starts_with("2 files matched (lines and matches not counted)\n0 files searched\n"));
+    }
+}
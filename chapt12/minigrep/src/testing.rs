@@ -0,0 +1,19 @@
+//! Helpers shared by the unit tests.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// A path in the temp directory that only this test run uses.
+pub fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("minigrep-{}-{}", process::id(), name))
+}
+
+/// An empty directory at [`temp_path`], with anything an earlier run left
+/// there cleared out.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = temp_path(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,435 @@
+//! Finding timestamps in log lines, for `--since` and `--until`.
+//!
+//! Three kinds are recognised near the start of a line:
+//!
+//! ```text
+//! 2024-05-01T14:03:22.512Z        RFC 3339, or with a space instead of the T
+//! May  1 14:03:22                 syslog
+//! [01/May/2024:14:03:22 +0000]    Apache common log
+//! ```
+//!
+//! Times are compared the way they're written. UTC offsets are ignored, and
+//! so is the year whenever one side doesn't have one, as syslog leaves it
+//! out.
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// How far into a line a timestamp can start.
+const SCAN_LIMIT: usize = 100;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+const MONTHS: [&[u8]; 12] = [
+    b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+];
+
+/// A date and time of day, as read from a log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: Option<u16>,
+    pub month: u8,
+    pub day: u8,
+    /// Nanoseconds since midnight.
+    pub time: u64,
+}
+
+impl Timestamp {
+    /// Compares two timestamps, leaving the year out unless both have one.
+    pub fn compare(&self, other: &Timestamp) -> Ordering {
+        let years = match (self.year, other.year) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => Ordering::Equal,
+        };
+        years.then((self.month, self.day, self.time).cmp(&(other.month, other.day, other.time)))
+    }
+
+    /// Finds the first timestamp near the start of `line`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minigrep::timestamp::Timestamp;
+    ///
+    /// let line = r#"127.0.0.1 - - [10/Oct/2023:13:55:36 -0700] "GET / HTTP/1.1" 200"#;
+    /// let found = Timestamp::find(line).unwrap();
+    /// assert_eq!((Some(2023), 10, 10), (found.year, found.month, found.day));
+    /// assert!(Timestamp::find("no time here").is_none());
+    /// ```
+    pub fn find(line: &str) -> Option<Timestamp> {
+        let bytes = line.as_bytes();
+        (0..bytes.len().min(SCAN_LIMIT)).find_map(|i| {
+            // Timestamps don't start in the middle of a word or number.
+            if i > 0 && bytes[i - 1].is_ascii_alphanumeric() {
+                return None;
+            }
+            let rest = &bytes[i..];
+            rfc3339(rest)
+                .or_else(|| syslog(rest))
+                .or_else(|| apache(rest))
+        })
+    }
+}
+
+/// One end of a `--since`/`--until` window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    At(Timestamp),
+    /// A time of day with no date, which means that time on the day of the
+    /// first timestamp in the file.
+    TimeOfDay(u64),
+}
+
+impl Bound {
+    /// Parses `14:00`, `14:00:30`, `2024-05-01`, or a date and time like
+    /// `2024-05-01T14:00:00Z` (the `T` can be a space).
+    pub fn parse(s: &str) -> Option<Bound> {
+        let bytes = s.trim().as_bytes();
+        let mut cursor = Cursor::new(bytes);
+        if let Some(time) = cursor.time(false) {
+            if cursor.at_end() {
+                return Some(Bound::TimeOfDay(time));
+            }
+        }
+        let mut cursor = Cursor::new(bytes);
+        let (year, month, day) = cursor.date()?;
+        let mut at = Timestamp {
+            year: Some(year),
+            month,
+            day,
+            time: 0,
+        };
+        if !cursor.at_end() {
+            cursor.one_of(b"Tt ")?;
+            at.time = cursor.time(false)?;
+            cursor.zone();
+        }
+        cursor.at_end().then_some(Bound::At(at))
+    }
+
+    fn resolve(self, first: &Timestamp) -> Timestamp {
+        match self {
+            Bound::At(at) => at,
+            Bound::TimeOfDay(time) => Timestamp { time, ..*first },
+        }
+    }
+}
+
+/// The time window `--since` and `--until` ask for. `since` is inclusive and
+/// `until` isn't.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Window {
+    pub since: Option<Bound>,
+    pub until: Option<Bound>,
+}
+
+impl Window {
+    /// Whether `at` is in the window, with times of day taken to be on the
+    /// same day as `first`, the first timestamp in the file.
+    pub fn contains(&self, at: &Timestamp, first: &Timestamp) -> bool {
+        let after_since = self
+            .since
+            .is_none_or(|since| at.compare(&since.resolve(first)) != Ordering::Less);
+        let before_until = self
+            .until
+            .is_none_or(|until| at.compare(&until.resolve(first)) == Ordering::Less);
+        after_since && before_until
+    }
+
+    /// The runs of lines in `text` that are inside the window, as byte ranges
+    /// along with the number of each run's first line.
+    ///
+    /// A line without a timestamp, like the rest of a stack trace, has the
+    /// one from the line before it. Lines before the first timestamp are
+    /// never inside.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minigrep::timestamp::{Bound, Window};
+    ///
+    /// let log = "\
+    /// 2024-05-01 13:59:59 ERROR early
+    /// 2024-05-01 14:00:01 ERROR boom
+    ///     at main.rs:10
+    /// 2024-05-01 14:20:00 INFO late
+    /// ";
+    /// let window = Window {
+    ///     since: Bound::parse("14:00"),
+    ///     until: Bound::parse("14:15"),
+    /// };
+    /// let runs = window.runs(log);
+    /// assert_eq!(1, runs.len());
+    /// assert_eq!(2, runs[0].0);
+    /// assert!(log[runs[0].1.clone()].ends_with("main.rs:10\n"));
+    /// ```
+    pub fn runs(&self, text: &str) -> Vec<(usize, Range<usize>)> {
+        let mut runs: Vec<(usize, Range<usize>)> = Vec::new();
+        let mut first = None;
+        let mut last = None;
+        let mut offset = 0;
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            let start = offset;
+            offset += line.len();
+            if let Some(at) = Timestamp::find(line) {
+                first.get_or_insert(at);
+                last = Some(at);
+            }
+            let inside = match (&last, &first) {
+                (Some(at), Some(first)) => self.contains(at, first),
+                _ => false,
+            };
+            if !inside {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((_, run)) if run.end == start => run.end = offset,
+                _ => runs.push((i + 1, start..offset)),
+            }
+        }
+        runs
+    }
+}
+
+/// Reads the pieces of a timestamp off the front of some bytes.
+#[derive(Debug, Clone, Copy)]
+struct Cursor<'b> {
+    bytes: &'b [u8],
+    at: usize,
+}
+
+impl<'b> Cursor<'b> {
+    fn new(bytes: &'b [u8]) -> Cursor<'b> {
+        Cursor { bytes, at: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.at == self.bytes.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.at).copied()
+    }
+
+    fn one_of(&mut self, set: &[u8]) -> Option<u8> {
+        let byte = self.peek().filter(|b| set.contains(b))?;
+        self.at += 1;
+        Some(byte)
+    }
+
+    /// Between `min` and `max` digits, as a number.
+    fn digits(&mut self, min: usize, max: usize) -> Option<u32> {
+        let start = self.at;
+        let mut n = 0;
+        while self.at - start < max {
+            match self.peek() {
+                Some(b @ b'0'..=b'9') => n = n * 10 + u32::from(b - b'0'),
+                _ => break,
+            }
+            self.at += 1;
+        }
+        (self.at - start >= min).then_some(n)
+    }
+
+    /// A number that has to be in `range`.
+    fn number(&mut self, min: usize, max: usize, range: Range<u32>) -> Option<u32> {
+        self.digits(min, max).filter(|n| range.contains(n))
+    }
+
+    fn month_name(&mut self) -> Option<u8> {
+        let name = self.bytes.get(self.at..self.at + 3)?;
+        let month = MONTHS.iter().position(|m| *m == name)?;
+        self.at += 3;
+        Some(month as u8 + 1)
+    }
+
+    /// `YYYY-MM-DD`.
+    fn date(&mut self) -> Option<(u16, u8, u8)> {
+        let year = self.digits(4, 4)?;
+        self.one_of(b"-")?;
+        let month = self.number(2, 2, 1..13)?;
+        self.one_of(b"-")?;
+        let day = self.number(2, 2, 1..32)?;
+        Some((year as u16, month as u8, day as u8))
+    }
+
+    /// `HH:MM`, then `:SS` (which can be required) and a fraction of a second
+    /// after a `.` or `,`. Returns nanoseconds since midnight.
+    fn time(&mut self, need_seconds: bool) -> Option<u64> {
+        let hours = self.number(1, 2, 0..24)?;
+        self.one_of(b":")?;
+        let minutes = self.number(2, 2, 0..60)?;
+        let mut seconds = 0;
+        let mut nanos = 0;
+        let mut ahead = *self;
+        match ahead.one_of(b":").and_then(|_| ahead.number(2, 2, 0..61)) {
+            Some(s) => {
+                seconds = s;
+                *self = ahead;
+                let mut fraction = *self;
+                if fraction.one_of(b".,").is_some() {
+                    let start = fraction.at;
+                    if let Some(n) = fraction.digits(1, 9) {
+                        let places = (fraction.at - start) as u32;
+                        nanos = u64::from(n) * 10u64.pow(9 - places);
+                        // Anything finer than nanoseconds is dropped.
+                        while fraction.one_of(b"0123456789").is_some() {}
+                        *self = fraction;
+                    }
+                }
+            }
+            None if need_seconds => return None,
+            None => {}
+        }
+        let seconds = u64::from(hours * 3600 + minutes * 60 + seconds);
+        Some(seconds * NANOS_PER_SECOND + nanos)
+    }
+
+    /// An optional `Z`, `+HH:MM` or `+HHMM` UTC offset, which is skipped.
+    fn zone(&mut self) {
+        if self.one_of(b"Zz").is_some() {
+            return;
+        }
+        let mut ahead = *self;
+        if ahead.one_of(b"+-").is_some() && ahead.digits(2, 2).is_some() {
+            ahead.one_of(b":");
+            if ahead.digits(2, 2).is_some() {
+                *self = ahead;
+            }
+        }
+    }
+}
+
+/// `2024-05-01T14:03:22.512Z`, or with a space instead of the `T`.
+fn rfc3339(bytes: &[u8]) -> Option<Timestamp> {
+    let mut cursor = Cursor::new(bytes);
+    let (year, month, day) = cursor.date()?;
+    cursor.one_of(b"Tt ")?;
+    let time = cursor.time(true)?;
+    Some(Timestamp {
+        year: Some(year),
+        month,
+        day,
+        time,
+    })
+}
+
+/// `May  1 14:03:22`, with the day padded by a space or a zero.
+fn syslog(bytes: &[u8]) -> Option<Timestamp> {
+    let mut cursor = Cursor::new(bytes);
+    let month = cursor.month_name()?;
+    cursor.one_of(b" ")?;
+    cursor.one_of(b" ");
+    let day = cursor.number(1, 2, 1..32)?;
+    cursor.one_of(b" ")?;
+    let time = cursor.time(true)?;
+    Some(Timestamp {
+        year: None,
+        month,
+        day: day as u8,
+        time,
+    })
+}
+
+/// `01/May/2024:14:03:22`, which is inside the brackets in Apache's logs.
+fn apache(bytes: &[u8]) -> Option<Timestamp> {
+    let mut cursor = Cursor::new(bytes);
+    let day = cursor.number(2, 2, 1..32)?;
+    cursor.one_of(b"/")?;
+    let month = cursor.month_name()?;
+    cursor.one_of(b"/")?;
+    let year = cursor.digits(4, 4)?;
+    cursor.one_of(b":")?;
+    let time = cursor.time(true)?;
+    Some(Timestamp {
+        year: Some(year as u16),
+        month,
+        day: day as u8,
+        time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: Option<u16>, month: u8, day: u8, h: u64, m: u64, s: u64) -> Timestamp {
+        Timestamp {
+            year,
+            month,
+            day,
+            time: (h * 3600 + m * 60 + s) * NANOS_PER_SECOND,
+        }
+    }
+
+    #[test]
+    fn finds_each_kind_of_timestamp() {
+        let found = |line| Timestamp::find(line).unwrap();
+        assert_eq!(
+            at(Some(2024), 5, 1, 14, 3, 22),
+            found("2024-05-01T14:03:22Z ERROR boom")
+        );
+        let python = found("2024-05-01 14:03:22,512 WARNING x");
+        assert_eq!(512_000_000, python.time % NANOS_PER_SECOND);
+        assert_eq!(
+            at(None, 5, 1, 4, 3, 2),
+            found("May  1 04:03:02 host sshd[42]: Accepted")
+        );
+        let leap = found(r#"{"time": "2024-12-09T23:59:60.5+01:00"}"#);
+        assert_eq!((Some(2024), 12, 9), (leap.year, leap.month, leap.day));
+        assert_eq!(86_400_500_000_000, leap.time);
+
+        assert_eq!(None, Timestamp::find("v2024-05-01T14:03:22"));
+        assert_eq!(None, Timestamp::find("2024-13-01T14:03:22"));
+        assert_eq!(None, Timestamp::find("Mayday 1 14:03:22"));
+        let late = format!("{}2024-05-01T14:03:22", " ".repeat(SCAN_LIMIT));
+        assert_eq!(None, Timestamp::find(&late));
+    }
+
+    #[test]
+    fn parses_bounds() {
+        assert_eq!(
+            Some(Bound::TimeOfDay(14 * 3600 * NANOS_PER_SECOND)),
+            Bound::parse("14:00")
+        );
+        assert_eq!(
+            Some(Bound::At(at(Some(2024), 5, 1, 0, 0, 0))),
+            Bound::parse("2024-05-01")
+        );
+        assert_eq!(
+            Some(Bound::At(at(Some(2024), 5, 1, 14, 15, 0))),
+            Bound::parse("2024-05-01T14:15:00+0200")
+        );
+        assert_eq!(None, Bound::parse("2pm"));
+        assert_eq!(None, Bound::parse("14:00 tomorrow"));
+    }
+
+    #[test]
+    fn windows_carry_timestamps_forward_and_ignore_missing_years() {
+        let log = "\
+preamble
+May  1 13:00:00 a
+May  1 14:05:00 b
+  continued
+May  1 14:10:00 c
+May  1 14:30:00 d
+  continued
+";
+        let window = Window {
+            since: Bound::parse("2024-05-01 14:00"),
+            until: Bound::parse("14:30"),
+        };
+        let runs: Vec<_> = window
+            .runs(log)
+            .into_iter()
+            .map(|(line, range)| (line, &log[range]))
+            .collect();
+        assert_eq!(
+            vec![(3, "May  1 14:05:00 b\n  continued\nMay  1 14:10:00 c\n")],
+            runs
+        );
+        assert!(Window::default()
+            .runs(log)
+            .iter()
+            .all(|(line, _)| *line == 2));
+    }
+}
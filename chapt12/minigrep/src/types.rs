@@ -0,0 +1,198 @@
+//! File types for `-t` and `-T`: names like `rust` or `markdown` that stand
+//! for a handful of globs.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::glob::{Glob, GlobError};
+
+/// The types minigrep knows about out of the box.
+pub const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("css", &["*.css", "*.scss"]),
+    ("csv", &["*.csv", "*.tsv"]),
+    ("docker", &["Dockerfile", "*.dockerfile"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json", "*.jsonl"]),
+    ("log", &["*.log"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh", ".bashrc", ".zshrc"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// A table of file types plus which of them were asked for.
+///
+/// With nothing selected every file matches. Otherwise a file has to match
+/// one of the `-t` types, if there are any, and none of the `-T` ones.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::types::Types;
+///
+/// let mut types = Types::new();
+/// types.add("proto:*.proto").unwrap();
+/// types.select("proto").unwrap();
+/// assert!(types.is_match("api/user.proto"));
+/// assert!(!types.is_match("src/main.rs"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Types {
+    definitions: BTreeMap<String, Vec<Glob>>,
+    selected: Vec<Glob>,
+    negated: Vec<Glob>,
+}
+
+/// Something wrong with a `-t`, `-T` or `--type-add`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    /// There's no type with this name.
+    Unknown(String),
+    /// A `--type-add` that isn't `NAME:GLOB`.
+    BadDefinition(String),
+    Glob(GlobError),
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeError::Unknown(name) => write!(f, "unknown file type {:?}", name),
+            TypeError::BadDefinition(def) => {
+                write!(f, "file type {:?} should look like NAME:GLOB", def)
+            }
+            TypeError::Glob(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+impl Default for Types {
+    fn default() -> Types {
+        let definitions = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| {
+                let globs = globs.iter().map(|g| Glob::new(g).unwrap()).collect();
+                (name.to_string(), globs)
+            })
+            .collect();
+        Types {
+            definitions,
+            selected: Vec::new(),
+            negated: Vec::new(),
+        }
+    }
+}
+
+impl Types {
+    /// The built-in types, with none selected.
+    pub fn new() -> Types {
+        Types::default()
+    }
+
+    /// Adds a glob to a type, creating the type if it's new. `definition`
+    /// looks like `NAME:GLOB`.
+    pub fn add(&mut self, definition: &str) -> Result<(), TypeError> {
+        let (name, glob) = definition
+            .split_once(':')
+            .filter(|(name, glob)| !name.is_empty() && !glob.is_empty())
+            .ok_or_else(|| TypeError::BadDefinition(definition.to_string()))?;
+        let glob = Glob::new(glob).map_err(TypeError::Glob)?;
+        self.definitions
+            .entry(name.to_string())
+            .or_default()
+            .push(glob);
+        Ok(())
+    }
+
+    /// Only match files of type `name` (or of any other selected type).
+    pub fn select(&mut self, name: &str) -> Result<(), TypeError> {
+        let globs = self.globs(name)?;
+        self.selected.extend(globs);
+        Ok(())
+    }
+
+    /// Never match files of type `name`.
+    pub fn negate(&mut self, name: &str) -> Result<(), TypeError> {
+        let globs = self.globs(name)?;
+        self.negated.extend(globs);
+        Ok(())
+    }
+
+    fn globs(&self, name: &str) -> Result<Vec<Glob>, TypeError> {
+        self.definitions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| TypeError::Unknown(name.to_string()))
+    }
+
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        (self.selected.is_empty() || self.selected.iter().any(|g| g.is_match(path)))
+            && !self.negated.iter().any(|g| g.is_match(path))
+    }
+}
+
+impl fmt::Display for Types {
+    /// The table printed by `--type-list`, one `name: glob, glob` per line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, globs) in &self.definitions {
+            let globs: Vec<_> = globs.iter().map(Glob::as_str).collect();
+            writeln!(f, "{}: {}", name, globs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_and_negates_types() {
+        let mut types = Types::new();
+        assert!(types.is_match("anything.xyz"));
+
+        types.select("rust").unwrap();
+        types.select("toml").unwrap();
+        assert!(types.is_match("src/lib.rs"));
+        assert!(types.is_match("Cargo.toml"));
+        assert!(!types.is_match("README.md"));
+
+        let mut types = Types::new();
+        types.negate("markdown").unwrap();
+        assert!(types.is_match("src/lib.rs"));
+        assert!(!types.is_match("README.md"));
+
+        assert_eq!(
+            Err(TypeError::Unknown("cobol".to_string())),
+            types.select("cobol")
+        );
+    }
+
+    #[test]
+    fn added_types_show_up_in_the_list() {
+        let mut types = Types::new();
+        types.add("rust:*.rs.in").unwrap();
+        assert!(types.add("nocolon").is_err());
+        assert!(types.add("bad:[").is_err());
+
+        let list = types.to_string();
+        assert!(list.contains("rust: *.rs, *.rs.in\n"));
+        assert!(list.contains("make: Makefile, makefile, GNUmakefile, *.mk\n"));
+    }
+}
@@ -0,0 +1,367 @@
+//! Turning the paths given on the command line into a list of files.
+//!
+//! Files are passed straight through. Directories are walked recursively, in
+//! sorted order so results always come out the same way. Symlinks found while
+//! walking are skipped unless [`Walk::follow_links`] is set, and minigrep's own
+//! index files always are. The other setters limit how deep the walk goes,
+//! how big a file can be, whether it may cross into other filesystems and
+//! which file types it finds.
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::follow::{file_id, FileId};
+use crate::index::INDEX_FILE;
+use crate::types::Types;
+
+/// Lazily yields every file under the given paths.
+///
+/// # Examples
+///
+/// ```no_run
+/// use minigrep::walk::Walk;
+///
+/// for file in Walk::new(["src"]).max_depth(Some(2)) {
+///     println!("{}", file.unwrap().display());
+/// }
+/// ```
+pub struct Walk {
+    /// Paths still to visit, in reverse so the next one is on the end.
+    stack: Vec<Pending>,
+    follow_links: bool,
+    max_depth: Option<usize>,
+    max_filesize: Option<u64>,
+    one_file_system: bool,
+    types: Types,
+    /// Directories read so far and when they were last modified, if asked for.
+    dirs: Option<Vec<(PathBuf, Option<SystemTime>)>>,
+}
+
+/// A path waiting to be visited.
+struct Pending {
+    path: PathBuf,
+    /// 0 for the paths the walk started from, 1 for what's directly in them...
+    depth: usize,
+    /// The device the starting path is on, once it's known.
+    root_dev: Option<u64>,
+    /// The directories this path was found under, to spot symlink loops.
+    ancestors: Rc<Vec<FileId>>,
+}
+
+impl Walk {
+    pub fn new<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Walk {
+        let mut stack: Vec<Pending> = paths
+            .into_iter()
+            .map(|p| Pending {
+                path: p.as_ref().to_path_buf(),
+                depth: 0,
+                root_dev: None,
+                ancestors: Rc::default(),
+            })
+            .collect();
+        stack.reverse();
+        Walk {
+            stack,
+            follow_links: false,
+            max_depth: None,
+            max_filesize: None,
+            one_file_system: false,
+            types: Types::new(),
+            dirs: None,
+        }
+    }
+
+    /// Follow symlinks found while walking. A link back to a directory
+    /// that's already being walked is reported as an error, not followed.
+    pub fn follow_links(mut self, yes: bool) -> Walk {
+        self.follow_links = yes;
+        self
+    }
+
+    /// Don't descend more than `depth` directories below the starting paths.
+    /// With `Some(0)` only files given directly are yielded.
+    pub fn max_depth(mut self, depth: Option<usize>) -> Walk {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Skip files bigger than `bytes`. They come out as errors for which
+    /// [`WalkError::is_too_large`] is true, so they can still be counted.
+    pub fn max_filesize(mut self, bytes: Option<u64>) -> Walk {
+        self.max_filesize = bytes;
+        self
+    }
+
+    /// Don't cross into other filesystems from the one each starting path is
+    /// on. Only supported on Unix.
+    pub fn one_file_system(mut self, yes: bool) -> Walk {
+        self.one_file_system = yes;
+        self
+    }
+
+    /// Keep a list of the directories read, for [`Walk::dirs`].
+    pub fn record_dirs(mut self, yes: bool) -> Walk {
+        self.dirs = yes.then(Vec::new);
+        self
+    }
+
+    /// Every directory read so far, with its modification time at the point
+    /// it was read. If none of them have changed since, walking again would
+    /// find the same files. Empty unless [`Walk::record_dirs`] is set.
+    pub fn dirs(&self) -> &[(PathBuf, Option<SystemTime>)] {
+        self.dirs.as_deref().unwrap_or_default()
+    }
+
+    /// Only yield files found in directories if they match `types`. Paths
+    /// given directly are always yielded.
+    pub fn types(mut self, types: Types) -> Walk {
+        self.types = types;
+        self
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<PathBuf, WalkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Pending {
+            path,
+            depth,
+            root_dev,
+            ancestors,
+        }) = self.stack.pop()
+        {
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(err) => return Some(Err(WalkError { path, err })),
+            };
+            let id = file_id(&metadata);
+            let dev = id.map(|id| id.dev);
+            if self.one_file_system && root_dev.is_some() && dev != root_dev {
+                continue;
+            }
+
+            if !metadata.is_dir() {
+                if depth > 0 && !self.types.is_match(&path) {
+                    continue;
+                }
+                if let Some(max) = self.max_filesize.filter(|&max| metadata.len() > max) {
+                    let err = io::Error::new(
+                        io::ErrorKind::FileTooLarge,
+                        format!("bigger than the {} byte limit", max),
+                    );
+                    return Some(Err(WalkError { path, err }));
+                }
+                return Some(Ok(path));
+            }
+
+            if id.is_some_and(|id| ancestors.contains(&id)) {
+                let err = io::Error::other("symlink loop, not following it again");
+                return Some(Err(WalkError { path, err }));
+            }
+            if self.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+
+            if let Some(dirs) = &mut self.dirs {
+                dirs.push((path.clone(), metadata.modified().ok()));
+            }
+            let entries = match read_dir_sorted(&path) {
+                Ok(entries) => entries,
+                Err(err) => return Some(Err(WalkError { path, err })),
+            };
+            let ancestors = Rc::new(ancestors.iter().copied().chain(id).collect::<Vec<_>>());
+            for entry in entries.into_iter().rev() {
+                let is_link = fs::symlink_metadata(&entry)
+                    .map(|m| m.file_type().is_symlink())
+                    .unwrap_or(false);
+                if (is_link && !self.follow_links) || is_index_file(&entry) {
+                    continue;
+                }
+                self.stack.push(Pending {
+                    path: entry,
+                    depth: depth + 1,
+                    root_dev: root_dev.or(dev),
+                    ancestors: Rc::clone(&ancestors),
+                });
+            }
+        }
+        None
+    }
+}
+
+/// A path that couldn't be visited.
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub err: io::Error,
+}
+
+impl std::fmt::Display for WalkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.err)
+    }
+}
+
+impl std::error::Error for WalkError {}
+
+impl WalkError {
+    /// Whether this is a file that was skipped for going over
+    /// [`Walk::max_filesize`] rather than a real error.
+    pub fn is_too_large(&self) -> bool {
+        self.err.kind() == io::ErrorKind::FileTooLarge
+    }
+}
+
+/// Reads a list of paths for `--files-from`, one per line or, if there are
+/// any NUL bytes, one per NUL. Empty entries are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::walk::read_paths;
+///
+/// let paths = read_paths("a b.txt\0new\nline.txt\0".as_bytes()).unwrap();
+/// assert_eq!(vec!["a b.txt", "new\nline.txt"], paths);
+/// ```
+pub fn read_paths(mut reader: impl Read) -> io::Result<Vec<String>> {
+    let mut list = String::new();
+    reader.read_to_string(&mut list)?;
+    let separator = if list.contains('\0') { '\0' } else { '\n' };
+    Ok(list
+        .split(separator)
+        .map(|path| path.strip_suffix('\r').unwrap_or(path))
+        .filter(|path| !path.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Parses a size like `512`, `64K`, `10M` or `2G`. The suffixes are powers
+/// of 1024.
+///
+/// # Examples
+///
+/// ```
+/// use minigrep::walk::parse_size;
+///
+/// assert_eq!(Some(10 * 1024 * 1024), parse_size("10M"));
+/// assert_eq!(None, parse_size("ten"));
+/// ```
+pub fn parse_size(size: &str) -> Option<u64> {
+    let (digits, shift) = match size.char_indices().last()? {
+        (i, 'k' | 'K') => (&size[..i], 10),
+        (i, 'm' | 'M') => (&size[..i], 20),
+        (i, 'g' | 'G') => (&size[..i], 30),
+        _ => (size, 0),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn is_index_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            name.strip_prefix(INDEX_FILE)
+                .is_some_and(|rest| rest.is_empty() || rest == ".tmp")
+        })
+}
+
+fn read_dir_sorted(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    #[test]
+    fn walks_directories_in_sorted_order() {
+        let root = temp_dir("walk");
+        fs::create_dir_all(root.join("b/c")).unwrap();
+        fs::write(root.join("b/c/deep.txt"), "").unwrap();
+        fs::write(root.join("b/one.txt"), "").unwrap();
+        fs::write(root.join("a.txt"), "").unwrap();
+
+        let files: Vec<_> = Walk::new([&root])
+            .map(|f| f.unwrap().strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            vec![
+                PathBuf::from("a.txt"),
+                PathBuf::from("b/c/deep.txt"),
+                PathBuf::from("b/one.txt"),
+            ],
+            files
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn limits_depth_and_file_size() {
+        let root = temp_dir("walk-limits");
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/deep.txt"), "").unwrap();
+        fs::write(root.join("a/big.txt"), "x".repeat(100)).unwrap();
+        fs::write(root.join("small.txt"), "x").unwrap();
+
+        let walk = Walk::new([&root]).max_depth(Some(2)).max_filesize(Some(10));
+        let (files, errors): (Vec<_>, Vec<_>) = walk.partition(Result::is_ok);
+        assert_eq!(
+            vec![root.join("small.txt")],
+            files.into_iter().map(Result::unwrap).collect::<Vec<_>>()
+        );
+        let error = errors.into_iter().next().unwrap().unwrap_err();
+        assert!(error.is_too_large());
+        assert_eq!(root.join("a/big.txt"), error.path);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn following_links_stops_at_loops() {
+        let root = temp_dir("walk-links");
+        fs::create_dir_all(root.join("dir")).unwrap();
+        fs::write(root.join("dir/file.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("dir/loop")).unwrap();
+
+        assert_eq!(1, Walk::new([&root]).count());
+
+        let results: Vec<_> = Walk::new([&root]).follow_links(true).collect();
+        assert_eq!(2, results.len());
+        assert_eq!(&root.join("dir/file.txt"), results[0].as_ref().unwrap());
+        assert_eq!(root.join("dir/loop"), results[1].as_ref().unwrap_err().path);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn sizes_take_binary_suffixes() {
+        assert_eq!(Some(512), parse_size("512"));
+        assert_eq!(Some(64 * 1024), parse_size("64k"));
+        assert_eq!(Some(2 << 30), parse_size("2G"));
+        assert_eq!(None, parse_size("M"));
+        assert_eq!(None, parse_size("-1"));
+        assert_eq!(None, parse_size(""));
+    }
+
+    #[test]
+    fn missing_paths_are_errors() {
+        let mut walk = Walk::new(["definitely/not/here.txt"]);
+
+        let err = walk.next().unwrap().unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.err.kind());
+        assert!(walk.next().is_none());
+    }
+}
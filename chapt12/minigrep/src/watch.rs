@@ -0,0 +1,203 @@
+//! Noticing when the files being searched change, for `--watch`.
+//!
+//! A [`Snapshot`] records the size and modification time of every file under
+//! the searched paths. Comparing two snapshots says which files were created,
+//! modified or deleted in between, and [`diff`] compares the matches found
+//! before and after.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::walk::Walk;
+
+/// How long `minigrep --watch` waits between snapshots.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// The metadata of every file under some paths at one point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    files: BTreeMap<PathBuf, Stamp>,
+}
+
+impl Snapshot {
+    /// Records every file `walk` finds. Files that can't be read are left
+    /// out, so they'll show up as created once they can be.
+    pub fn take(walk: Walk) -> Snapshot {
+        let files = walk
+            .filter_map(Result::ok)
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                let stamp = Stamp {
+                    modified: metadata.modified().ok(),
+                    len: metadata.len(),
+                };
+                Some((path, stamp))
+            })
+            .collect();
+        Snapshot { files }
+    }
+
+    /// The files in the snapshot, in sorted order.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Lists what changed between `older` and this snapshot.
+    pub fn changes_since(&self, older: &Snapshot) -> Changes {
+        let mut changes = Changes::default();
+        for (path, stamp) in &self.files {
+            match older.files.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(old) if old != stamp => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        for path in older.files.keys() {
+            if !self.files.contains_key(path) {
+                changes.deleted.push(path.clone());
+            }
+        }
+        changes
+    }
+}
+
+/// Files that changed between two snapshots.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub created: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} created, {} modified, {} deleted",
+            self.created.len(),
+            self.modified.len(),
+            self.deleted.len()
+        )
+    }
+}
+
+/// A matching line, owned so it can outlive the search that found it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub path: PathBuf,
+    pub line_number: usize,
+    pub text: String,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.path.display(),
+            self.line_number,
+            self.text
+        )
+    }
+}
+
+/// Matches that appeared or disappeared between two runs.
+#[derive(Debug, Default, PartialEq)]
+pub struct HitDiff<'a> {
+    pub appeared: Vec<&'a Hit>,
+    pub disappeared: Vec<&'a Hit>,
+}
+
+/// Compares the hits from two runs of the same search.
+///
+/// Hits are matched up by file and text rather than line number, so a line
+/// that merely moved because something was inserted above it doesn't count
+/// as a change.
+pub fn diff<'a>(old: &'a [Hit], new: &'a [Hit]) -> HitDiff<'a> {
+    fn only_in<'a>(these: &'a [Hit], those: &[Hit]) -> Vec<&'a Hit> {
+        let mut counts: HashMap<(&Path, &str), usize> = HashMap::new();
+        for hit in those {
+            *counts.entry((&hit.path, &hit.text)).or_default() += 1;
+        }
+        these
+            .iter()
+            .filter(
+                |hit| match counts.get_mut(&(hit.path.as_path(), hit.text.as_str())) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                },
+            )
+            .collect()
+    }
+
+    HitDiff {
+        appeared: only_in(new, old),
+        disappeared: only_in(old, new),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    fn hit(path: &str, line_number: usize, text: &str) -> Hit {
+        Hit {
+            path: PathBuf::from(path),
+            line_number,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn snapshots_notice_created_modified_and_deleted_files() {
+        let root = temp_dir("watch");
+        fs::write(root.join("stays.txt"), "same").unwrap();
+        fs::write(root.join("grows.txt"), "short").unwrap();
+        fs::write(root.join("goes.txt"), "bye").unwrap();
+        let before = Snapshot::take(Walk::new([&root]));
+
+        fs::write(root.join("grows.txt"), "a bit longer").unwrap();
+        fs::remove_file(root.join("goes.txt")).unwrap();
+        fs::write(root.join("new.txt"), "hi").unwrap();
+        let changes = Snapshot::take(Walk::new([&root])).changes_since(&before);
+
+        assert_eq!(vec![root.join("new.txt")], changes.created);
+        assert_eq!(vec![root.join("grows.txt")], changes.modified);
+        assert_eq!(vec![root.join("goes.txt")], changes.deleted);
+        assert_eq!("1 created, 1 modified, 1 deleted", changes.to_string());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn moved_lines_are_not_differences() {
+        let old = vec![hit("a.rs", 1, "todo"), hit("a.rs", 4, "todo again")];
+        let new = vec![
+            hit("a.rs", 2, "todo"),
+            hit("a.rs", 3, "todo"),
+            hit("b.rs", 1, "todo"),
+        ];
+
+        let diff = diff(&old, &new);
+        assert_eq!(vec![&new[1], &new[2]], diff.appeared);
+        assert_eq!(vec![&old[1]], diff.disappeared);
+    }
+}
@@ -0,0 +1,106 @@
+/* Exercises the C API in include/minigrep.h. Run by tests/ffi.rs. */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "minigrep.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                 \
+            failures++;                                                     \
+        }                                                                   \
+    } while (0)
+
+#define MAX_FOUND 8
+
+struct found {
+    MinigrepMatch matches[MAX_FOUND];
+    size_t count;
+    /* Stop after this many, or never if it's 0. */
+    size_t stop_after;
+};
+
+static bool collect(const MinigrepMatch *m, void *user_data) {
+    struct found *found = user_data;
+    if (found->count < MAX_FOUND) {
+        found->matches[found->count] = *m;
+    }
+    found->count++;
+    return found->stop_after == 0 || found->count < found->stop_after;
+}
+
+static MinigrepMatcher *compile(const char *pattern, bool regex,
+                                bool case_insensitive) {
+    MinigrepOptions options = {pattern, regex, case_insensitive, false};
+    char *error = NULL;
+    MinigrepMatcher *matcher = minigrep_matcher_new(&options, &error);
+    CHECK(matcher != NULL);
+    CHECK(error == NULL);
+    return matcher;
+}
+
+static const char *poem =
+    "I'm nobody! Who are you?\n"
+    "Are you nobody, too?\n"
+    "Then there's a pair of us - don't tell!\n";
+
+static void finds_matches_with_line_numbers_and_offsets(void) {
+    MinigrepMatcher *matcher = compile("NOBODY", false, true);
+    struct found found = {0};
+    int64_t n = minigrep_search(matcher, poem, strlen(poem), collect, &found);
+    CHECK(n == 2);
+    CHECK(found.count == 2);
+
+    const MinigrepMatch *second = &found.matches[1];
+    CHECK(second->line_number == 2);
+    CHECK(second->end_line_number == 2);
+    CHECK(strncmp(poem + second->start, "nobody", 6) == 0);
+    CHECK(second->end - second->start == 6);
+    CHECK(second->lines_len == strlen("Are you nobody, too?"));
+    CHECK(strncmp(second->lines, "Are you nobody, too?", second->lines_len) == 0);
+    minigrep_matcher_free(matcher);
+}
+
+static void regexes_and_stopping_early(void) {
+    MinigrepMatcher *matcher = compile("[Aa]re|pair", true, false);
+    struct found found = {0};
+    found.stop_after = 2;
+    int64_t n = minigrep_search(matcher, poem, strlen(poem), collect, &found);
+    CHECK(n == 2);
+    CHECK(found.matches[0].line_number == 1);
+    CHECK(found.matches[1].line_number == 2);
+    minigrep_matcher_free(matcher);
+}
+
+static void reports_errors(void) {
+    MinigrepOptions options = {"(unclosed", true, false, false};
+    char *error = NULL;
+    CHECK(minigrep_matcher_new(&options, &error) == NULL);
+    CHECK(error != NULL && strlen(error) > 0);
+    minigrep_error_free(error);
+
+    MinigrepMatcher *matcher = compile("x", false, false);
+    const char not_utf8[] = {'x', (char)0xff, '\n'};
+    struct found found = {0};
+    CHECK(minigrep_search(matcher, not_utf8, sizeof not_utf8, collect, &found) == -1);
+    CHECK(minigrep_search(matcher, "", 0, collect, &found) == 0);
+    CHECK(found.count == 0);
+    minigrep_matcher_free(matcher);
+    minigrep_matcher_free(NULL);
+}
+
+int main(void) {
+    finds_matches_with_line_numbers_and_offsets();
+    regexes_and_stopping_early();
+    reports_errors();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    return 0;
+}
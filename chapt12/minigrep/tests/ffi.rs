@@ -0,0 +1,68 @@
+//! Checks the C API: that `include/minigrep.h` is what cbindgen makes of
+//! `src/ffi.rs`, and that `tests/ffi.c` builds against the cdylib and passes.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn header_is_up_to_date() {
+    let dir = manifest_dir();
+    let config = cbindgen::Config::from_file(dir.join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(dir.join("src/ffi.rs"))
+        .generate()
+        .expect("couldn't generate the header");
+    let mut generated = Vec::new();
+    bindings.write(&mut generated);
+
+    let header = dir.join("include/minigrep.h");
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&header, &generated).unwrap();
+    }
+    let committed = fs::read(&header).unwrap_or_default();
+    assert!(
+        committed == generated,
+        "include/minigrep.h is out of date, run the tests with UPDATE_HEADER=1 to regenerate it"
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn c_test_passes() {
+    use std::process::Command;
+
+    let dir = manifest_dir();
+    // The cdylib cargo built along with this test, in the same directory.
+    let library = env::current_exe().unwrap().with_file_name("libminigrep.so");
+    assert!(library.exists(), "{} wasn't built", library.display());
+    let binary = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg("-I")
+        .arg(dir.join("include"))
+        .arg(dir.join("tests/ffi.c"))
+        .arg(&library)
+        .arg(format!(
+            "-Wl,-rpath,{}",
+            library.parent().unwrap().display()
+        ))
+        .arg("-o")
+        .arg(&binary)
+        .status()
+        .unwrap_or_else(|e| panic!("couldn't run {}: {}", compiler, e));
+    assert!(status.success(), "tests/ffi.c didn't compile");
+
+    let output = Command::new(&binary).output().unwrap();
+    assert!(
+        output.status.success(),
+        "tests/ffi.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}